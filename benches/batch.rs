@@ -0,0 +1,59 @@
+// cargo bench --bench batch
+// WorldBatch against stepping the same games one by one through Env::step, same seeds and actions.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use snake_game::{Env, GameConfig, Level, WorldBatch};
+
+const GAMES: usize = 256;
+const STEPS: usize = 2000;
+const MAX_STEPS: usize = 500;
+
+fn actions(step: usize) -> Vec<u8> {
+	(0..GAMES).map(|n| ((n + step / 7) % 4) as u8).collect()
+}
+
+fn batch(level: &Level) -> Duration {
+	let mut batch = WorldBatch::from_level(level, GameConfig::default(), GAMES, MAX_STEPS, 1);
+	let start = Instant::now();
+	for step in 0..STEPS {
+		batch.step(&actions(step));
+		black_box(batch.observations());
+	}
+	start.elapsed()
+}
+
+fn looped(level: &Level) -> Duration {
+	let mut envs: Vec<Env> = (0..GAMES).map(|_| Env::new(level.clone(), GameConfig::default(), MAX_STEPS)).collect();
+	let mut observations: Vec<_> = envs.iter_mut().enumerate().map(|(n, env)| env.reset(1 + n as u64)).collect();
+	let mut next_seed = 1 + GAMES as u64;
+	let start = Instant::now();
+	for step in 0..STEPS {
+		for (n, action) in actions(step).into_iter().enumerate() {
+			let (observation, _, done, _) = envs[n].step(action as usize);
+			observations[n] = if done {
+				next_seed += 1;
+				envs[n].reset(next_seed)
+			} else {
+				observation
+			};
+		}
+		black_box(&observations);
+	}
+	start.elapsed()
+}
+
+fn main() {
+	for size in [10, 20, 40] {
+		let level = Level::new(size, size, size * size / 2);
+		let looped = looped(&level);
+		let batch = batch(&level);
+		println!(
+			"{size}x{size}, {GAMES} games x {STEPS} steps: loop {:.1} ms, batch {:.1} ms, {:.1}x",
+			looped.as_secs_f64() * 1000.0,
+			batch.as_secs_f64() * 1000.0,
+			looped.as_secs_f64() / batch.as_secs_f64()
+		);
+	}
+}
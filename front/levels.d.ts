@@ -0,0 +1,5 @@
+// level files are bundled as plain text, see webpack.config.js
+declare module "*.txt" {
+	const content: string;
+	export default content;
+}
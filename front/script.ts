@@ -1,5 +1,8 @@
 import "./styles.css";
-import init, { WorldMap, Direction, GameStatus } from "snake_game";
+import init, { WorldMap, Direction, GameStatus, ControllerKind } from "snake_game";
+import classicLevel from "./levels/classic.txt";
+import duelLevel from "./levels/duel.txt";
+import portalsLevel from "./levels/portals.txt";
 
 init().then((wasmObj) => {
 	const canvas = <HTMLCanvasElement> document.getElementById("snake-game-canvas");
@@ -13,16 +16,24 @@ init().then((wasmObj) => {
 	const ctx = canvas.getContext("2d");
 	
 	const CELL_SIZE = 100; // px
-	const MAP_SIZE = 8;
-	const snakeSpawnIdx = Date.now() % (MAP_SIZE * MAP_SIZE);
-	const SPEED = 1700; // ms
 
-	const map = WorldMap.new(MAP_SIZE, snakeSpawnIdx);
+	// ?duel starts the two player board, ?bot the same board against the computer, ?portals the board with obstacles
+	const params = new URLSearchParams(location.search);
+	const level = params.has("duel") || params.has("bot") ? duelLevel : params.has("portals") ? portalsLevel : classicLevel;
+	// the classic board has no spawn, the snake starts anywhere
+	const map = WorldMap.from_level_str_with_spawn(level, Date.now() % 1000000);
+	if (params.has("bot")) {
+		map.set_controller(1, params.get("bot") === "survival" ? ControllerKind.Survival : ControllerKind.Greedy);
+	}
+	const PLAYERS = map.player_count();
+	const MAP_WIDTH = map.width();
+	const MAP_HEIGHT = map.height();
 
-	const lineLength = MAP_SIZE * CELL_SIZE;
+	const mapWidth = MAP_WIDTH * CELL_SIZE;
+	const mapHeight = MAP_HEIGHT * CELL_SIZE;
 
-	canvas.height = lineLength;
-	canvas.width = lineLength;
+	canvas.height = mapHeight;
+	canvas.width = mapWidth;
 
 	document.addEventListener("keyup", (e) => {
 		const gameStatus = map.game_status();
@@ -32,28 +43,50 @@ init().then((wasmObj) => {
 			gameControlBtn.textContent = "Reload";
 		}
 
+		if (e.code === "KeyH") {
+			showHint = !showHint;
+			paint();
+			return;
+		}
+		if (e.code === "KeyP") {
+			map.set_autopilot(!map.autopilot());
+			return;
+		}
+		// player 1 plays with WASD, player 2 with the arrows, alone (or against the computer) both work
+		const arrowsPlayer = PLAYERS > 1 && map.player_controller(1) === undefined ? 1 : 0;
 		switch (e.code) {
 			case "KeyW":
-			case "ArrowUp":
-				map.change_snake_direction(Direction.Up);
+				map.change_player_direction(0, Direction.Up);
 				break;
 			case "KeyS":
-			case "ArrowDown":
-				map.change_snake_direction(Direction.Down);
+				map.change_player_direction(0, Direction.Down);
 				break;
 			case "KeyA":
-			case "ArrowLeft":
-				map.change_snake_direction(Direction.Left);
+				map.change_player_direction(0, Direction.Left);
 				break;
 			case "KeyD":
+				map.change_player_direction(0, Direction.Right);
+				break;
+			case "ArrowUp":
+				map.change_player_direction(arrowsPlayer, Direction.Up);
+				break;
+			case "ArrowDown":
+				map.change_player_direction(arrowsPlayer, Direction.Down);
+				break;
+			case "ArrowLeft":
+				map.change_player_direction(arrowsPlayer, Direction.Left);
+				break;
 			case "ArrowRight":
-				map.change_snake_direction(Direction.Right);
+				map.change_player_direction(arrowsPlayer, Direction.Right);
 				break;
 			default:
 				break;
 		}
 	})
 
+	// H shows where suggest_direction would go, P lets the autopilot play player 1
+	let showHint = false;
+
 	let touchStartX: null | number = null;
 	let touchStartY: null | number = null;
 
@@ -108,21 +141,46 @@ init().then((wasmObj) => {
 	function drawMap() {
 		ctx.beginPath();
 		// drow columns
-		for(let x = 0; x < MAP_SIZE + 1; x++) {
+		for(let x = 0; x < MAP_WIDTH + 1; x++) {
 			const line = CELL_SIZE * x;
 			ctx.moveTo(line, 0);
-			ctx.lineTo(line, lineLength)
+			ctx.lineTo(line, mapHeight)
 		}
 		// drow rows
-		for(let y = 0; y < MAP_SIZE + 1; y++) {
+		for(let y = 0; y < MAP_HEIGHT + 1; y++) {
 			const line = CELL_SIZE * y;
 			ctx.moveTo(0, line);
-			ctx.lineTo(lineLength, line);
+			ctx.lineTo(mapWidth, line);
 		}
 		ctx.stroke();
 	}
 	
-	function drawSnake() {
+	const PLAYER_COLORS = [
+		{head: "#7878db", body: "#9100db"},
+		{head: "#db9c78", body: "#db5b00"}
+	];
+
+	// paint() reads everything from one render_state() call, the records are described in src/render.rs
+	const RENDER_STATE_VERSION = 1;
+	const RECORD_WORDS = 7;
+	// u32::MAX, a missing cell, status or winner
+	const RENDER_NONE = 4294967295;
+	const RecordKind = {Header: 0, Snake: 1, Reward: 2, Trap: 3, SuperBonus: 4, Enemy: 5, Portal: 6, Obstacle: 7};
+	// RewardType order, traps pick from them too, a missing color (RENDER_NONE) falls back
+	const ITEM_COLORS = ["#FFEAAE", "chocolate", "blueviolet", "brown"];
+	// TargetStatus order
+	const ENEMY_STATUS_TEXT = ["Hungry, almost dead", "Just Hungry", "I'm fine!", "Full of energy", "God mode"];
+
+	function readRenderState(): Uint32Array {
+		const pointer = map.render_state();
+		const header = new Uint32Array(wasmObj.memory.buffer, pointer, RECORD_WORDS);
+		if (header[1] !== RENDER_STATE_VERSION) {
+			throw new Error(`render state version ${header[1]}, expected ${RENDER_STATE_VERSION}`);
+		}
+		return new Uint32Array(wasmObj.memory.buffer, pointer, header[2] * RECORD_WORDS);
+	}
+
+	function drawSnake(player: number, snakeLength: number, alive: boolean, status: number) {
 		const snakeBodyColors = [
 			{color: "lightgreen", xFactor: 0.75, yFactor: 0.7, radius: 0.09},
 			{color: "pink", xFactor: 0.25, yFactor: 0.33, radius: 0.1},
@@ -130,8 +188,9 @@ init().then((wasmObj) => {
 			{color: "lightblue", xFactor: 0.71, yFactor: 0.4, radius: 0.13},
 			{color: "gray", xFactor: 0.33, yFactor: 0.7, radius: 0.09}
 		]
-		const snakeCellPointer = map.snake_cells();
-		const snakeLength = map.snake_length();
+		const snakeCellPointer = map.player_cells(player);
+		const colors = PLAYER_COLORS[player % PLAYER_COLORS.length];
+		const dead = PLAYERS > 1 ? !alive : status === GameStatus.Lost;
 		const snakeCells = new Uint32Array(
 			wasmObj.memory.buffer, 
 			snakeCellPointer, // offset
@@ -142,10 +201,10 @@ init().then((wasmObj) => {
 			.reverse() // mutate array in memory
 			.forEach((cell, i) => {
 			ctx.beginPath();
-			const xCoord = (cell %  MAP_SIZE) * CELL_SIZE;
-			const yCoord = Math.floor(cell / MAP_SIZE) * CELL_SIZE;
+			const xCoord = (cell % MAP_WIDTH) * CELL_SIZE;
+			const yCoord = Math.floor(cell / MAP_WIDTH) * CELL_SIZE;
 			
-			ctx.fillStyle = i === snakeLength - 1 ? "#7878db" : map.game_status() == GameStatus.Lost ? "black" : "#9100db";
+			ctx.fillStyle = i === snakeLength - 1 ? colors.head : dead ? "black" : colors.body;
 			
 			ctx.fillRect(xCoord, yCoord, CELL_SIZE, CELL_SIZE); // will draw starting from (x,y) coord
 			ctx.stroke();
@@ -162,16 +221,33 @@ init().then((wasmObj) => {
 		});
 	}
 
-	function drawReward() {
-		const rewardIdx = map.reward_cell_idx() ;
-		const col = rewardIdx % MAP_SIZE;
-		const row = Math.floor(rewardIdx / MAP_SIZE);
-		const text = map.get_reward_points().toString() + "p";
-		const bonusP = map.comming_bonus_by_steps()
+	function drawObstacle(cell: number) {
+		ctx.fillStyle = "dimgray";
+		ctx.fillRect((cell % MAP_WIDTH) * CELL_SIZE, Math.floor(cell / MAP_WIDTH) * CELL_SIZE, CELL_SIZE, CELL_SIZE);
+	}
+
+	function drawPortal(entry: number, exit: number, pair: number) {
+		[entry, exit].forEach(cell => {
+			const col = cell % MAP_WIDTH;
+			const row = Math.floor(cell / MAP_WIDTH);
+			ctx.beginPath();
+			ctx.strokeStyle = pair % 2 === 0 ? "darkorange" : "teal";
+			ctx.lineWidth = 6;
+			ctx.arc(col * CELL_SIZE + .5 * CELL_SIZE, row * CELL_SIZE + .5 * CELL_SIZE, CELL_SIZE * 0.35, 0, 2 * Math.PI);
+			ctx.stroke();
+			ctx.lineWidth = 1;
+			ctx.strokeStyle = "black";
+		});
+	}
+
+	function drawReward(rewardIdx: number, points: number, color: number, bonusP: number) {
+		const col = rewardIdx % MAP_WIDTH;
+		const row = Math.floor(rewardIdx / MAP_WIDTH);
+		const text = points.toString() + "p";
 		const text2 = bonusP ? "+" + bonusP.toString() + "p" : "";
 
 		ctx.beginPath();
-		ctx.fillStyle = map.get_reward_color() || "#FFEAAE";
+		ctx.fillStyle = ITEM_COLORS[color] ?? "cadetblue";
 		ctx.arc(col * CELL_SIZE + .5 * CELL_SIZE, row * CELL_SIZE + .5 * CELL_SIZE, CELL_SIZE / 2, 0, 2 * Math.PI);
 		ctx.fill();
 
@@ -186,85 +262,76 @@ init().then((wasmObj) => {
 		overlayContainer.style.display = "none";
 	}	
 
-	function drawTrap() {
-		const trapInx = map.trap_cell_idx();
-		if (trapInx !== 1000000 ) {
-			const trap_color = map.trap_color();
-			const col = trapInx % MAP_SIZE;
-			const row = Math.floor(trapInx / MAP_SIZE);
+	function drawTrap(trapInx: number, life: number, color: number) {
+		const col = trapInx % MAP_WIDTH;
+		const row = Math.floor(trapInx / MAP_WIDTH);
 
-			ctx.beginPath();
-			ctx.fillStyle = trap_color !== "None" ? trap_color : "red";
-			ctx.arc(col * CELL_SIZE + .5 * CELL_SIZE, row * CELL_SIZE + .5 * CELL_SIZE, CELL_SIZE / 2, 0, 2 * Math.PI);
-			ctx.fill();
-
-			ctx.fillStyle = "white";
-			ctx.font = "15px Arial";
-			const text = map.trap_life().toString() + "p";
-			ctx.fillText(text, col * CELL_SIZE + CELL_SIZE * 0.45, row * CELL_SIZE + CELL_SIZE * 0.55);
-
-			ctx.fillStyle = "red";
-			ctx.arc(col * CELL_SIZE + .5 * CELL_SIZE, row * CELL_SIZE + .5 * CELL_SIZE, CELL_SIZE / 2, 0, 2 * Math.PI);
-			ctx.stroke();		
-		}
+		ctx.beginPath();
+		ctx.fillStyle = ITEM_COLORS[color] ?? "red";
+		ctx.arc(col * CELL_SIZE + .5 * CELL_SIZE, row * CELL_SIZE + .5 * CELL_SIZE, CELL_SIZE / 2, 0, 2 * Math.PI);
+		ctx.fill();
+
+		ctx.fillStyle = "white";
+		ctx.font = "15px Arial";
+		const text = life.toString() + "p";
+		ctx.fillText(text, col * CELL_SIZE + CELL_SIZE * 0.45, row * CELL_SIZE + CELL_SIZE * 0.55);
+
+		ctx.fillStyle = "red";
+		ctx.arc(col * CELL_SIZE + .5 * CELL_SIZE, row * CELL_SIZE + .5 * CELL_SIZE, CELL_SIZE / 2, 0, 2 * Math.PI);
+		ctx.stroke();		
 	}
 
-	function drawSuperBonus() {
-		const superBonusIdx = map.super_bonus_cell_idx();
-		if (superBonusIdx !== 1000000 ) {
-			const col = superBonusIdx % MAP_SIZE;
-			const row = Math.floor(superBonusIdx / MAP_SIZE);
-
-			const points = 5;
-			const radius = CELL_SIZE / 2;
-			const centerX = col * CELL_SIZE + .5 * CELL_SIZE;
-			const centerY = row * CELL_SIZE + .5 * CELL_SIZE;
-			ctx.fillStyle = "yellow";
-			// circle
-			ctx.beginPath();
-			ctx.arc(col * CELL_SIZE + .5 * CELL_SIZE, row * CELL_SIZE + .5 * CELL_SIZE, CELL_SIZE / 2, 0, 2 * Math.PI);
-			ctx.fill();
-			// start
-			ctx.beginPath();
-			ctx.strokeStyle = "red"
-			ctx.moveTo(centerX + radius, centerY - radius );
-			for (let i = 0; i <= points; i++) {
-				const angle = Math.PI * 2 * i / 5 - Math.PI / 2;
-				const x = centerX + Math.cos(angle) * radius;
-				const y = centerY + Math.sin(angle) * radius;
-				ctx.lineTo(x, y);
-				const innerAngle = Math.PI * 2 * (i + 0.5) / 5 - Math.PI / 2;
-				const innerX = centerX + Math.cos(innerAngle) * radius / 2;
-				const innerY = centerY + Math.sin(innerAngle) * radius / 2;
-				ctx.lineTo(innerX, innerY);
-			}
-			ctx.stroke();
+	function drawSuperBonus(superBonusIdx: number, life: number, bonusPoints: number) {
+		const col = superBonusIdx % MAP_WIDTH;
+		const row = Math.floor(superBonusIdx / MAP_WIDTH);
 
-			ctx.fillStyle = "red";
-			ctx.font = "15px Arial";
-			const text = map.super_bonus_points().toString() + "p";
-			ctx.fillText(text, col * CELL_SIZE + CELL_SIZE * 0.3, row * CELL_SIZE + CELL_SIZE * 0.45);
-			const text2 = map.super_bonus_cell_life().toString();
-			ctx.fillText(text2, col * CELL_SIZE + CELL_SIZE * 0.4, row * CELL_SIZE + CELL_SIZE * 0.65);
+		const points = 5;
+		const radius = CELL_SIZE / 2;
+		const centerX = col * CELL_SIZE + .5 * CELL_SIZE;
+		const centerY = row * CELL_SIZE + .5 * CELL_SIZE;
+		ctx.fillStyle = "yellow";
+		// circle
+		ctx.beginPath();
+		ctx.arc(col * CELL_SIZE + .5 * CELL_SIZE, row * CELL_SIZE + .5 * CELL_SIZE, CELL_SIZE / 2, 0, 2 * Math.PI);
+		ctx.fill();
+		// start
+		ctx.beginPath();
+		ctx.strokeStyle = "red"
+		ctx.moveTo(centerX + radius, centerY - radius );
+		for (let i = 0; i <= points; i++) {
+			const angle = Math.PI * 2 * i / 5 - Math.PI / 2;
+			const x = centerX + Math.cos(angle) * radius;
+			const y = centerY + Math.sin(angle) * radius;
+			ctx.lineTo(x, y);
+			const innerAngle = Math.PI * 2 * (i + 0.5) / 5 - Math.PI / 2;
+			const innerX = centerX + Math.cos(innerAngle) * radius / 2;
+			const innerY = centerY + Math.sin(innerAngle) * radius / 2;
+			ctx.lineTo(innerX, innerY);
 		}
+		ctx.stroke();
+
+		ctx.fillStyle = "red";
+		ctx.font = "15px Arial";
+		const text = bonusPoints.toString() + "p";
+		ctx.fillText(text, col * CELL_SIZE + CELL_SIZE * 0.3, row * CELL_SIZE + CELL_SIZE * 0.45);
+		const text2 = life.toString();
+		ctx.fillText(text2, col * CELL_SIZE + CELL_SIZE * 0.4, row * CELL_SIZE + CELL_SIZE * 0.65);
 	}
 
-	function drawMovingTarget() {
-		const targetCell = map.moving_target_cell_idx();
-		const xCoord = (targetCell %  MAP_SIZE) * CELL_SIZE;
-		const yCoord = Math.floor(targetCell / MAP_SIZE) * CELL_SIZE;
-		//const targetPoints 
+	function drawMovingTarget(targetCell: number, points: number, status: string) {
+		const xCoord = (targetCell % MAP_WIDTH) * CELL_SIZE;
+		const yCoord = Math.floor(targetCell / MAP_WIDTH) * CELL_SIZE;
 		
 		ctx.beginPath();
 		ctx.fillStyle = "#7edd9e";
 		ctx.fillRect(xCoord, yCoord, CELL_SIZE, CELL_SIZE); 
 		ctx.fillStyle = "black";
 		ctx.font = "15px Arial";
-		const text = map.moving_target_points().toString() + "p";
+		const text = points.toString() + "p";
 		ctx.fillText(text, xCoord + CELL_SIZE * 0.3, yCoord + CELL_SIZE * 0.55);
 		const text2 = "Enemy";
 		ctx.fillText(text2, xCoord + CELL_SIZE * 0.3, yCoord + CELL_SIZE * 0.2);		
-		const text3 = map.moving_target_status();
+		const text3 = status;
 		let fontSize = 15;
 		let textWidth = ctx.measureText(text3).width;
 		while (textWidth > CELL_SIZE) {
@@ -276,77 +343,148 @@ init().then((wasmObj) => {
 	}
 
 
-	function drawGameStatus() {
-		gameStatusContainer.textContent = map.game_status_text();
-		gamePointsContainer.textContent = map.points().toString() ;
-		gameStepsContainer.textContent = `Points reduce after (${map.get_steps()}) steps.`;
-		gameBonusesContainer.textContent = map.bonus_stat().toString();
+	function drawHint(head: number) {
+		const direction = showHint ? map.suggest_direction() : undefined;
+		if (direction === undefined) {
+			return;
+		}
+		const centerX = (head % MAP_WIDTH) * CELL_SIZE + .5 * CELL_SIZE;
+		const centerY = Math.floor(head / MAP_WIDTH) * CELL_SIZE + .5 * CELL_SIZE;
+		const angle = {
+			[Direction.Up]: -Math.PI / 2,
+			[Direction.Right]: 0,
+			[Direction.Down]: Math.PI / 2,
+			[Direction.Left]: Math.PI
+		}[direction];
+		ctx.save();
+		ctx.translate(centerX, centerY);
+		ctx.rotate(angle);
+		ctx.beginPath();
+		ctx.fillStyle = "white";
+		ctx.moveTo(CELL_SIZE * 0.45, 0);
+		ctx.lineTo(CELL_SIZE * 0.2, -CELL_SIZE * 0.15);
+		ctx.lineTo(CELL_SIZE * 0.2, CELL_SIZE * 0.15);
+		ctx.fill();
+		ctx.restore();
 	}
 
-	function paint() {
-		drawMap();
-		drawSnake();
-		drawReward();
-		drawGameStatus();
-		drawTrap();
-		drawSuperBonus();
-		drawMovingTarget();
+	// same text as map.game_status_text(), built from the header so a frame doesn't allocate a string in wasm
+	function gameStatusText(status: number, winner: number): string {
+		if (PLAYERS > 1) {
+			if (status === GameStatus.Won && winner !== RENDER_NONE) {
+				return `Player ${winner + 1} has won!`;
+			}
+			if (status === GameStatus.Lost) {
+				return "Draw!";
+			}
+		}
+		switch (status) {
+			case GameStatus.Won: return "You have won!";
+			case GameStatus.Lost: return "You have lost!";
+			case GameStatus.Played: return "Game ongoing";
+			default: return "Pause";
+		}
 	}
 
-	function defineFPS() {
-		let snakeLength = map.snake_length();
-		switch(true) {
-			case snakeLength <= 6:
-				return 3;
-			case snakeLength > 6 && snakeLength <= 9:
-				return 3.5;
-			case snakeLength > 9 && snakeLength <= 12:
-				return 4;
-			case snakeLength > 12 && snakeLength <= 15:
-				return 4.5;
-			case snakeLength > 15 && snakeLength <= 18:
-				return 5;
-			case snakeLength > 18 && snakeLength <= 25:
-				return 5.3;
-			case snakeLength > 25 && snakeLength <= 30:
-				return 5.6;
-			case snakeLength > 30 && snakeLength <= 35:
-				return 6.1;
-			case snakeLength > 35 && snakeLength <= 40:
-				return 7;
-			default:
-				return 4.8;
+	function drawGameStatus(status: number, winner: number, points: number[], steps: number, bonus: number) {
+		gameStatusContainer.textContent = gameStatusText(status, winner);
+		gamePointsContainer.textContent = PLAYERS > 1
+			? points.map((p, i) => `P${i + 1}: ${p}`).join(" / ")
+			: points[0].toString() ;
+		gameStepsContainer.textContent = `Points reduce after (${steps}) steps.`;
+		gameBonusesContainer.textContent = bonus.toString();
+	}
+
+	function paint() {
+		const state = readRenderState();
+		const word = (record: number, n: number) => state[record * RECORD_WORDS + n];
+		const records = word(0, 2);
+		const status = word(0, 3);
+		drawMap();
+		// obstacles and portals under everything else, they come last in the buffer
+		for (let r = 1; r < records; r++) {
+			if (word(r, 0) === RecordKind.Obstacle) {
+				drawObstacle(word(r, 1));
+			} else if (word(r, 0) === RecordKind.Portal) {
+				drawPortal(word(r, 1), word(r, 2), word(r, 3));
+			}
 		}
+		const points: number[] = [];
+		// cell in word 1, the kind's data from word 2 on
+		for (let r = 1; r < records; r++) {
+			const cell = word(r, 1);
+			switch (word(r, 0)) {
+				case RecordKind.Snake:
+					drawSnake(word(r, 2), word(r, 3), word(r, 4) === 1, status);
+					points.push(word(r, 5));
+					break;
+				case RecordKind.Reward:
+					drawReward(cell, word(r, 2), word(r, 3), word(r, 4));
+					break;
+				case RecordKind.Trap:
+					drawTrap(cell, word(r, 2), word(r, 3));
+					break;
+				case RecordKind.SuperBonus:
+					drawSuperBonus(cell, word(r, 2), word(r, 3));
+					break;
+				case RecordKind.Enemy:
+					drawMovingTarget(cell, word(r, 4), ENEMY_STATUS_TEXT[word(r, 3)]);
+					break;
+			}
+		}
+		drawGameStatus(status, word(0, 4), points, word(0, 5), word(0, 6));
+		drawHint(word(1, 1));
 	}
 
+	let lastFrame = 0;
+
 	function start() {
-		let fps = defineFPS();
-		setTimeout(()=> {
+		lastFrame = performance.now();
+		requestAnimationFrame(frame);
+	}
+
+	// the engine decides the speed, we only hand it the passed time
+	function frame(now: number) {
+		const elapsed = Math.floor(now - lastFrame);
+		lastFrame += elapsed;
+		if (map.advance(elapsed) > 0) {
 			ctx.clearRect(0,0, canvas.width, canvas.height); // cleaning canvas
-			map.update();
 			paint();
-			if (map.game_status() !== GameStatus.Played) {
-				gameReasonContainer.textContent = map.get_reason();
-				overlayContainer.style.display = "block";
-				const stat = map.get_game_stat();
-				overlayContainer.innerHTML += `
-					<div>Snake life time: ${stat.life_steps} steps </div> 
-					<div>Eated enemies: ${stat.consumed_moving_targets} ps</div>  
-					<div>Traps: ${stat.consumed_traps} ps </div>
-					<div>Targets: ${stat.consumed_rewards} ps </div>
-					<div>Super bonuses: ${stat.super_bonuses} </div>
-					<div>Bonus points: ${stat.bonus}p </div>
-					<div>Total points: <b>${stat.points}p </b> </div>
-					<div>Max snake length: ${stat.snake_size} </div>
-					<div>Eaten by enemy: ${stat.eaten_by_enemy} times </div>
-					<div>Final status: ${map.get_reason()} </div>
-					${map.get_reason().includes("enemy") ? "<div><b>Poinst are eaten by enemy!</b></div>" : ""} 
-				`;
+		}
+		if (map.game_status() !== GameStatus.Played) {
+			gameReasonContainer.textContent = map.get_reason();
+			overlayContainer.style.display = "block";
+			if (PLAYERS > 1) {
+				overlayContainer.innerHTML += `<div><b>${map.game_status_text()}</b></div>`;
+				for (let p = 0; p < PLAYERS; p++) {
+					const stat = map.player_stat(p);
+					overlayContainer.innerHTML += `
+						<div>Player ${p + 1}: <b>${stat.points}p</b>, max length ${stat.snake_size}, ${map.player_reason(p)} </div>
+					`;
+				}
+				overlayContainer.innerHTML += `<div>Life time: ${map.get_game_stat().life_steps} steps </div>`;
 				document.getElementById("close").addEventListener("click", hideStat, false);
 				return;
 			}
-			requestAnimationFrame(start);
-		}, SPEED / fps)
+			const stat = map.get_game_stat();
+			overlayContainer.innerHTML += `
+				<div>Snake life time: ${stat.life_steps} steps </div> 
+				<div>Eated enemies: ${stat.consumed_moving_targets} ps</div>  
+				<div>Traps: ${stat.consumed_traps} ps </div>
+				<div>Targets: ${stat.consumed_rewards} ps </div>
+				<div>Super bonuses: ${stat.super_bonuses} </div>
+				<div>Bonus points: ${stat.bonus}p </div>
+				<div>Total points: <b>${stat.points}p </b> </div>
+				<div>Max snake length: ${stat.snake_size} </div>
+				<div>Eaten by enemy: ${stat.eaten_by_enemy} times </div>
+				<div>Difficulty: ${map.difficulty_text()} </div>
+				<div>Final status: ${map.get_reason()} </div>
+				${map.get_reason().includes("enemy") ? "<div><b>Poinst are eaten by enemy!</b></div>" : ""} 
+			`;
+			document.getElementById("close").addEventListener("click", hideStat, false);
+			return;
+		}
+		requestAnimationFrame(frame);
 	}
 	paint();
 })
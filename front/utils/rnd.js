@@ -1,4 +1,3 @@
-export const now = Date.now; // for webassembly 
 export function rnd(max) {
 	return Math.floor(Math.random() * max);
 }
\ No newline at end of file
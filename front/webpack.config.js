@@ -29,6 +29,10 @@ module.exports = {
 					"css-loader"
 				],
 			},
+			{
+				test: /\.txt$/,
+				type: 'asset/source',
+			},
 			{
 				test: /\.tsx?$/,
 				use: 'ts-loader',
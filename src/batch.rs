@@ -0,0 +1,173 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::{Env, GameConfig, Level, LevelError};
+
+// Many independent games stepped together, one call per step for all of them.
+// Finished games start over right away with the next seed, their observation is already the new game.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct WorldBatch {
+	envs: Vec<Env>,
+	next_seed: u64,
+	episodes: usize,
+	// observation_len() values per game, same layout as Observation
+	observations: Vec<f32>,
+	// per game the observation values that are 1.0, a step only clears these and sets the new ones
+	lit: Vec<Vec<usize>>,
+	rewards: Vec<f32>,
+	// 0 still running, 1 game over, 2 cut at max_steps
+	dones: Vec<u8>
+}
+
+impl WorldBatch {
+	// game n starts with seed + n, resets take the following seeds
+	pub fn from_level(level: &Level, config: GameConfig, count: usize, max_steps: usize, seed: u64) -> WorldBatch {
+		let envs: Vec<Env> = (0..count).map(|_| Env::new(level.clone(), config, max_steps)).collect();
+		let observation_len = envs.first().map_or(0, |env| env.observation_len());
+		let mut batch = WorldBatch {
+			envs,
+			next_seed: seed,
+			episodes: 0,
+			observations: vec![0.0; count * observation_len],
+			lit: vec![vec!(); count],
+			rewards: vec![0.0; count],
+			dones: vec![0; count]
+		};
+		batch.reset(seed);
+		batch
+	}
+
+	pub fn observations(&self) -> &[f32] {
+		&self.observations
+	}
+
+	pub fn rewards(&self) -> &[f32] {
+		&self.rewards
+	}
+
+	pub fn dones(&self) -> &[u8] {
+		&self.dones
+	}
+
+	pub fn env(&self, n: usize) -> &Env {
+		&self.envs[n]
+	}
+
+	fn restart(&mut self, n: usize) {
+		self.envs[n].restart(self.next_seed);
+		self.next_seed = self.next_seed.wrapping_add(1);
+		self.write_observation(n);
+	}
+
+	// O(entities on the board) instead of O(Observation::CHANNELS * cells), the rest of the tensor stays 0.0
+	fn write_observation(&mut self, n: usize) {
+		let len = self.observation_len();
+		let out = &mut self.observations[n * len..(n + 1) * len];
+		let lit = &mut self.lit[n];
+		for pos in lit.drain(..) {
+			out[pos] = 0.0;
+		}
+		self.envs[n].lit_values(|pos| {
+			out[pos] = 1.0;
+			lit.push(pos);
+		});
+	}
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl WorldBatch {
+	pub fn new(size: usize, snake_idx: usize, config: GameConfig, count: usize, max_steps: usize, seed: u64) -> WorldBatch {
+		WorldBatch::from_level(&Level::new(size, size, snake_idx), config, count, max_steps, seed)
+	}
+
+	pub fn from_level_str(text: &str, config: GameConfig, count: usize, max_steps: usize, seed: u64) -> Result<WorldBatch, LevelError> {
+		Ok(WorldBatch::from_level(&Level::parse_with_snake_size(text, config.checked().snake_start_size)?, config, count, max_steps, seed))
+	}
+
+	pub fn reset(&mut self, seed: u64) {
+		self.next_seed = seed;
+		for n in 0..self.envs.len() {
+			self.restart(n);
+		}
+		self.rewards.fill(0.0);
+		self.dones.fill(0);
+	}
+
+	// one action per game, an index into Env::ACTIONS, anything else (or a missing one) keeps the direction
+	pub fn step(&mut self, actions: &[u8]) {
+		for n in 0..self.envs.len() {
+			let action = actions.get(n).map_or(usize::MAX, |action| *action as usize);
+			let (reward, over, truncated) = self.envs[n].play(action);
+			self.envs[n].clear_events();
+			self.rewards[n] = reward;
+			self.dones[n] = if over { 1 } else if truncated { 2 } else { 0 };
+			if over || truncated {
+				self.episodes += 1;
+				self.restart(n);
+			} else {
+				self.write_observation(n);
+			}
+		}
+	}
+
+	pub fn count(&self) -> usize {
+		self.envs.len()
+	}
+
+	pub fn observation_len(&self) -> usize {
+		self.envs.first().map_or(0, |env| env.observation_len())
+	}
+
+	// games finished (or cut) since the batch was made
+	pub fn episodes(&self) -> usize {
+		self.episodes
+	}
+
+	// for typed arrays over wasm memory: count() * observation_len() f32s, count() f32s and count() u8s
+	pub fn observations_ptr(&self) -> *const f32 {
+		self.observations.as_ptr()
+	}
+
+	pub fn rewards_ptr(&self) -> *const f32 {
+		self.rewards.as_ptr()
+	}
+
+	pub fn dones_ptr(&self) -> *const u8 {
+		self.dones.as_ptr()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn observations_match_the_games() {
+		let mut batch = WorldBatch::new(8, 20, GameConfig::default(), 6, 40, 1);
+		let len = batch.observation_len();
+		for step in 0..300 {
+			let actions: Vec<u8> = (0..6).map(|n| ((n + step / 3) % 5) as u8).collect();
+			batch.step(&actions);
+			for n in 0..batch.count() {
+				assert!(batch.observations()[n * len..(n + 1) * len] == batch.env(n).observation().data[..]);
+			}
+		}
+	}
+
+	#[test]
+	fn finished_games_start_over() {
+		let mut batch = WorldBatch::new(8, 20, GameConfig::default(), 4, 10, 100);
+		for _ in 0..9 {
+			batch.step(&[]);
+			assert!(batch.dones().iter().all(|done| *done != 2));
+		}
+		batch.step(&[]);
+		// a snake going straight on an empty board lives longer than 10 steps
+		assert_eq!(batch.dones(), [2, 2, 2, 2]);
+		assert_eq!(batch.episodes(), 4);
+		assert!(batch.env(0).world().get_game_stat().life_steps == 0);
+		// the next seeds, 104 and up
+		let fresh = Env::new(Level::new(8, 8, 20), GameConfig::default(), 10).reset(104);
+		assert!(fresh.data[..] == batch.observations()[..batch.observation_len()]);
+	}
+}
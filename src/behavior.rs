@@ -0,0 +1,219 @@
+use std::collections::VecDeque;
+
+use crate::rng::GameRng;
+use crate::{step_cell, BoundaryMode, Direction, MovingTarget, SnakeCell, TargetStatus};
+
+// the snake head closer than this scares every enemy that isn't at full strength
+const COWARD_DISTANCE: usize = 2;
+
+// Everything an enemy knows about the board when it picks where to go.
+pub struct EnemyView<'a> {
+	pub position: usize,
+	pub direction: Direction,
+	pub status: TargetStatus,
+	pub width: usize,
+	pub height: usize,
+	pub boundary_mode: BoundaryMode,
+	// obstacles and portals, sorted
+	pub blocked: &'a [usize],
+	pub snake: &'a [SnakeCell],
+	pub reward: Option<usize>,
+	pub super_bonus: Option<usize>,
+	pub trap: Option<usize>
+}
+
+impl EnemyView<'_> {
+	pub fn snake_head(&self) -> usize {
+		self.snake[0].0
+	}
+
+	pub fn snake_tail(&self) -> usize {
+		self.snake[self.snake.len() - 1].0
+	}
+
+	// enemies never walk into walls, obstacles, traps or the snake's mouth
+	pub fn is_safe(&self, idx: usize) -> bool {
+		self.blocked.binary_search(&idx).is_err() && self.trap != Some(idx) && idx != self.snake_head()
+	}
+
+	pub fn neighbour(&self, idx: usize, direction: Direction) -> Option<usize> {
+		step_cell(idx, direction, self.width, self.height, self.boundary_mode)
+	}
+
+	pub fn distance(&self, a: usize, b: usize) -> usize {
+		cell_distance(a, b, self.width, self.height, self.boundary_mode)
+	}
+
+	// first step of the shortest safe path to any target cell
+	pub fn first_step_towards(&self, is_target: impl Fn(usize) -> bool) -> Option<Direction> {
+		let mut first: Vec<Option<Direction>> = vec![None; self.width * self.height];
+		let mut seen = vec![false; self.width * self.height];
+		let mut queue = VecDeque::new();
+		seen[self.position] = true;
+		queue.push_back(self.position);
+		while let Some(idx) = queue.pop_front() {
+			for direction in [self.direction, Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+				let Some(next) = self.neighbour(idx, direction).filter(|next| !seen[*next] && self.is_safe(*next)) else {
+					continue;
+				};
+				seen[next] = true;
+				first[next] = first[idx].or(Some(direction));
+				if is_target(next) {
+					return first[next];
+				}
+				queue.push_back(next);
+			}
+		}
+		None
+	}
+}
+
+// steps between two cells ignoring obstacles, wraps around when the board does
+pub(crate) fn cell_distance(a: usize, b: usize, width: usize, height: usize, boundary_mode: BoundaryMode) -> usize {
+	let axis = |a: usize, b: usize, size: usize| {
+		let diff = a.abs_diff(b);
+		match boundary_mode {
+			BoundaryMode::Wrap => diff.min(size - diff),
+			BoundaryMode::Walls => diff
+		}
+	};
+	axis(a % width, b % width, width) + axis(a / width, b / width, height)
+}
+
+pub trait EnemyBehavior {
+	// None keeps the current direction
+	fn next_direction(&self, view: &EnemyView, rng: &mut dyn GameRng) -> Option<Direction>;
+}
+
+// the old wandering, turns at random now and then
+pub struct RandomWalk;
+
+impl EnemyBehavior for RandomWalk {
+	fn next_direction(&self, _view: &EnemyView, rng: &mut dyn GameRng) -> Option<Direction> {
+		match rng.rnd(10) {
+			0..=5 => Some(MovingTarget::decide_direction(rng)),
+			_ => None
+		}
+	}
+}
+
+// goes for the tail, the bite cuts the snake there
+pub struct Hunter;
+
+impl EnemyBehavior for Hunter {
+	fn next_direction(&self, view: &EnemyView, rng: &mut dyn GameRng) -> Option<Direction> {
+		let tail = view.snake_tail();
+		view.first_step_towards(|idx| idx == tail).or_else(|| RandomWalk.next_direction(view, rng))
+	}
+}
+
+// heads for the closest food, reward or super bonus
+pub struct Forager;
+
+impl EnemyBehavior for Forager {
+	fn next_direction(&self, view: &EnemyView, rng: &mut dyn GameRng) -> Option<Direction> {
+		view.first_step_towards(|idx| Some(idx) == view.reward || Some(idx) == view.super_bonus)
+			.or_else(|| RandomWalk.next_direction(view, rng))
+	}
+}
+
+// runs from the snake head, keeps going straight when that is as good as turning
+pub struct Coward;
+
+impl EnemyBehavior for Coward {
+	fn next_direction(&self, view: &EnemyView, _rng: &mut dyn GameRng) -> Option<Direction> {
+		let head = view.snake_head();
+		let mut best: Option<(Direction, usize)> = None;
+		for direction in [view.direction, Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+			let Some(next) = view.neighbour(view.position, direction).filter(|next| view.is_safe(*next)) else {
+				continue;
+			};
+			let distance = view.distance(next, head);
+			if best.is_none_or(|(_, best_distance)| distance > best_distance) {
+				best = Some((direction, distance));
+			}
+		}
+		best.map(|(direction, _)| direction)
+	}
+}
+
+// full enemies hunt, hungry ones look for food, all but the strongest run when the head gets close
+pub fn behavior_for(view: &EnemyView) -> &'static dyn EnemyBehavior {
+	if !matches!(view.status, TargetStatus::VeryGood) && view.distance(view.position, view.snake_head()) <= COWARD_DISTANCE {
+		return &Coward;
+	}
+	match view.status {
+		TargetStatus::VeryGood | TargetStatus::Good => &Hunter,
+		TargetStatus::Fine => &RandomWalk,
+		TargetStatus::Hungry | TargetStatus::VeryHungry => &Forager
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::rng::SeededRng;
+
+	// 8x8 wrapping board, the snake head is on 12 and its tail on 10
+	const SNAKE: [SnakeCell; 3] = [SnakeCell(12), SnakeCell(11), SnakeCell(10)];
+
+	fn view(position: usize, direction: Direction, status: TargetStatus, reward: Option<usize>) -> EnemyView<'static> {
+		EnemyView {
+			position,
+			direction,
+			status,
+			width: 8,
+			height: 8,
+			boundary_mode: BoundaryMode::Wrap,
+			blocked: &[],
+			snake: &SNAKE,
+			reward,
+			super_bonus: None,
+			trap: None
+		}
+	}
+
+	fn pick(view: &EnemyView) -> Option<Direction> {
+		behavior_for(view).next_direction(view, &mut SeededRng::new(1))
+	}
+
+	#[test]
+	fn status_picks_the_behavior() {
+		// far from the head full enemies go for the tail, hungry ones for the reward
+		let far = view(47, Direction::Left, TargetStatus::Good, Some(44));
+		assert!(pick(&far) == Some(Direction::Up));
+		let hungry = view(47, Direction::Left, TargetStatus::Hungry, Some(44));
+		assert!(pick(&hungry) == Some(Direction::Left));
+		let fine = view(47, Direction::Left, TargetStatus::Fine, Some(44));
+		assert!(pick(&fine) == RandomWalk.next_direction(&fine, &mut SeededRng::new(1)));
+
+		// close to the head only the strongest keeps hunting, around the head to the tail
+		let strong = view(14, Direction::Left, TargetStatus::VeryGood, Some(44));
+		assert!(pick(&strong) == Some(Direction::Right));
+		let scared = view(14, Direction::Left, TargetStatus::Hungry, Some(44));
+		assert!(pick(&scared) == Some(Direction::Up));
+	}
+
+	#[test]
+	fn coward_keeps_going_when_it_is_as_good() {
+		let straight = view(14, Direction::Right, TargetStatus::Good, None);
+		assert!(Coward.next_direction(&straight, &mut SeededRng::new(1)) == Some(Direction::Right));
+		let down = view(14, Direction::Down, TargetStatus::Good, None);
+		assert!(Coward.next_direction(&down, &mut SeededRng::new(1)) == Some(Direction::Down));
+	}
+
+	#[test]
+	fn paths_go_around_blocked_cells() {
+		let snake = [SnakeCell(7)];
+		let mut line = EnemyView { width: 8, height: 1, boundary_mode: BoundaryMode::Walls, blocked: &[2], snake: &snake, ..view(0, Direction::Right, TargetStatus::Fine, None) };
+		assert!(line.first_step_towards(|idx| idx == 1) == Some(Direction::Right));
+		assert!(line.first_step_towards(|idx| idx == 3).is_none());
+		// nor through the snake's mouth
+		line.blocked = &[];
+		assert!(line.first_step_towards(|idx| idx == 7).is_none());
+		assert!(line.first_step_towards(|idx| idx == 6) == Some(Direction::Right));
+		line.boundary_mode = BoundaryMode::Wrap;
+		line.trap = Some(1);
+		assert!(line.first_step_towards(|idx| idx == 6).is_none());
+	}
+}
@@ -0,0 +1,131 @@
+use std::ops::Deref;
+
+use crate::SnakeCell;
+
+// Snake cells from head to tail in a ring buffer that holds every cell twice, at i and i + capacity.
+// Moving is O(1) (a new head, the tail is dropped by the length) and head..tail is always
+// one contiguous slice, JS reads it in place through player_cells / player_length.
+pub(crate) struct SnakeBody {
+	// 2 * capacity cells
+	cells: Vec<SnakeCell>,
+	// where the head is, below capacity
+	head: usize,
+	len: usize
+}
+
+impl SnakeBody {
+	pub fn new(cells: &[SnakeCell]) -> SnakeBody {
+		let capacity = (cells.len() * 2).max(8);
+		let mut ring = vec![SnakeCell(0); capacity * 2];
+		ring[..cells.len()].copy_from_slice(cells);
+		ring[capacity..capacity + cells.len()].copy_from_slice(cells);
+		SnakeBody {
+			cells: ring,
+			head: 0,
+			len: cells.len()
+		}
+	}
+
+	fn capacity(&self) -> usize {
+		self.cells.len() / 2
+	}
+
+	fn write(&mut self, pos: usize, cell: SnakeCell) {
+		let capacity = self.capacity();
+		let pos = pos % capacity;
+		self.cells[pos] = cell;
+		self.cells[pos + capacity] = cell;
+	}
+
+	// a full ring is laid out again twice as big, growing stays O(1) on average
+	fn reserve_one(&mut self) {
+		if self.len == self.capacity() {
+			*self = SnakeBody::new(&self[..]);
+		}
+	}
+
+	pub fn push_front(&mut self, cell: SnakeCell) {
+		self.reserve_one();
+		let capacity = self.capacity();
+		self.head = (self.head + capacity - 1) % capacity;
+		self.write(self.head, cell);
+		self.len += 1;
+	}
+
+	pub fn push_back(&mut self, cell: SnakeCell) {
+		self.reserve_one();
+		self.write(self.head + self.len, cell);
+		self.len += 1;
+	}
+
+	pub fn pop_back(&mut self) -> Option<SnakeCell> {
+		if self.len == 0 {
+			return None;
+		}
+		self.len -= 1;
+		Some(self.cells[self.head + self.len])
+	}
+
+	pub fn truncate(&mut self, len: usize) {
+		self.len = self.len.min(len);
+	}
+}
+
+impl Deref for SnakeBody {
+	type Target = [SnakeCell];
+
+	fn deref(&self) -> &[SnakeCell] {
+		&self.cells[self.head..self.head + self.len]
+	}
+}
+
+impl<'a> IntoIterator for &'a SnakeBody {
+	type Item = &'a SnakeCell;
+	type IntoIter = std::slice::Iter<'a, SnakeCell>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.iter()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::VecDeque;
+
+	use super::*;
+
+	fn cells(body: &SnakeBody) -> Vec<usize> {
+		body.iter().map(|cell| cell.0).collect()
+	}
+
+	#[test]
+	fn grows_past_its_capacity() {
+		let mut body = SnakeBody::new(&[SnakeCell(2), SnakeCell(1), SnakeCell(0)]);
+		for idx in 3..40 {
+			body.push_front(SnakeCell(idx));
+		}
+		body.push_back(SnakeCell(100));
+		assert_eq!(body.len(), 41);
+		assert_eq!(cells(&body)[..3], [39, 38, 37]);
+		assert_eq!(cells(&body)[38..], [1, 0, 100]);
+	}
+
+	#[test]
+	fn moving_wraps_around_the_ring() {
+		let mut body = SnakeBody::new(&[SnakeCell(1), SnakeCell(0)]);
+		let mut model: VecDeque<usize> = VecDeque::from([1, 0]);
+		for idx in 2..100 {
+			// a move, and now and then a meal
+			body.push_front(SnakeCell(idx));
+			model.push_front(idx);
+			if idx % 7 != 0 {
+				assert_eq!(body.pop_back().map(|cell| cell.0), model.pop_back());
+			}
+			assert_eq!(cells(&body), Vec::from(model.clone()));
+		}
+		body.truncate(3);
+		assert_eq!(cells(&body), [99, 98, 97]);
+		body.truncate(10);
+		assert_eq!(body.len(), 3);
+	}
+}
@@ -0,0 +1,168 @@
+use std::fmt;
+
+// Error for every binary or json format the game reads (replays, snapshots...)
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeError {
+	Truncated,
+	BadMagic,
+	UnsupportedVersion(u8),
+	Invalid(&'static str),
+	Json(String)
+}
+
+impl fmt::Display for DecodeError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			DecodeError::Truncated => write!(f, "data is truncated"),
+			DecodeError::BadMagic => write!(f, "unknown data format"),
+			DecodeError::UnsupportedVersion(version) => write!(f, "unsupported format version {}", version),
+			DecodeError::Invalid(what) => write!(f, "invalid {}", what),
+			DecodeError::Json(err) => write!(f, "invalid json: {}", err)
+		}
+	}
+}
+
+impl std::error::Error for DecodeError {}
+
+#[cfg(feature = "wasm")]
+impl From<DecodeError> for wasm_bindgen::JsValue {
+	fn from(err: DecodeError) -> wasm_bindgen::JsValue {
+		wasm_bindgen::JsError::new(&err.to_string()).into()
+	}
+}
+
+pub(crate) struct ByteWriter {
+	bytes: Vec<u8>
+}
+
+impl ByteWriter {
+	pub fn new(magic: &[u8; 4], version: u8) -> ByteWriter {
+		let mut bytes = magic.to_vec();
+		bytes.push(version);
+		ByteWriter { bytes }
+	}
+
+	pub fn u8(&mut self, value: u8) {
+		self.bytes.push(value);
+	}
+
+	pub fn u64(&mut self, value: u64) {
+		self.bytes.extend_from_slice(&value.to_le_bytes());
+	}
+
+	// LEB128, small numbers take one byte
+	pub fn varint(&mut self, value: usize) {
+		let mut value = value as u64;
+		loop {
+			let byte = (value & 0x7f) as u8;
+			value >>= 7;
+			if value == 0 {
+				self.bytes.push(byte);
+				return;
+			}
+			self.bytes.push(byte | 0x80);
+		}
+	}
+
+	pub fn bool(&mut self, value: bool) {
+		self.u8(value as u8);
+	}
+
+	pub fn bytes(&mut self, value: &[u8]) {
+		self.varint(value.len());
+		self.bytes.extend_from_slice(value);
+	}
+
+	pub fn string(&mut self, value: &str) {
+		self.bytes(value.as_bytes());
+	}
+
+	pub fn finish(self) -> Vec<u8> {
+		self.bytes
+	}
+}
+
+pub(crate) struct ByteReader<'a> {
+	bytes: &'a [u8],
+	pos: usize
+}
+
+impl<'a> ByteReader<'a> {
+	// checks the header, returns the reader and the format version
+	pub fn new(bytes: &'a [u8], magic: &[u8; 4]) -> Result<(ByteReader<'a>, u8), DecodeError> {
+		if bytes.len() < 5 {
+			return Err(if magic.starts_with(bytes) { DecodeError::Truncated } else { DecodeError::BadMagic });
+		}
+		if &bytes[..4] != magic {
+			return Err(DecodeError::BadMagic);
+		}
+		Ok((ByteReader { bytes, pos: 5 }, bytes[4]))
+	}
+
+	pub fn u8(&mut self) -> Result<u8, DecodeError> {
+		let byte = *self.bytes.get(self.pos).ok_or(DecodeError::Truncated)?;
+		self.pos += 1;
+		Ok(byte)
+	}
+
+	pub fn u64(&mut self) -> Result<u64, DecodeError> {
+		let end = self.pos + 8;
+		let slice = self.bytes.get(self.pos..end).ok_or(DecodeError::Truncated)?;
+		self.pos = end;
+		let mut buf = [0u8; 8];
+		buf.copy_from_slice(slice);
+		Ok(u64::from_le_bytes(buf))
+	}
+
+	pub fn varint(&mut self) -> Result<usize, DecodeError> {
+		let mut value: u64 = 0;
+		let mut shift = 0;
+		loop {
+			let byte = self.u8()?;
+			if shift >= 64 {
+				return Err(DecodeError::Invalid("varint"));
+			}
+			value |= ((byte & 0x7f) as u64) << shift;
+			if byte & 0x80 == 0 {
+				return usize::try_from(value).map_err(|_| DecodeError::Invalid("varint"));
+			}
+			shift += 7;
+		}
+	}
+
+	pub fn bool(&mut self) -> Result<bool, DecodeError> {
+		match self.u8()? {
+			0 => Ok(false),
+			1 => Ok(true),
+			_ => Err(DecodeError::Invalid("bool"))
+		}
+	}
+
+	pub fn bytes(&mut self) -> Result<&'a [u8], DecodeError> {
+		let len = self.list_len(1)?;
+		let slice = &self.bytes[self.pos..self.pos + len];
+		self.pos += len;
+		Ok(slice)
+	}
+
+	pub fn string(&mut self) -> Result<String, DecodeError> {
+		let bytes = self.bytes()?;
+		String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::Invalid("string"))
+	}
+
+	// list length, checked against what is left so corrupt data can't ask for huge allocations
+	pub fn list_len(&mut self, min_item_size: usize) -> Result<usize, DecodeError> {
+		let len = self.varint()?;
+		if len.saturating_mul(min_item_size.max(1)) > self.bytes.len() - self.pos {
+			return Err(DecodeError::Truncated);
+		}
+		Ok(len)
+	}
+
+	pub fn finish(self) -> Result<(), DecodeError> {
+		if self.pos != self.bytes.len() {
+			return Err(DecodeError::Invalid("trailing data"));
+		}
+		Ok(())
+	}
+}
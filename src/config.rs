@@ -0,0 +1,385 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::codec::{ByteReader, ByteWriter, DecodeError};
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+pub enum Difficulty {
+	Easy,
+	#[default]
+	Normal,
+	Hard,
+	Nightmare
+}
+
+impl Difficulty {
+	pub(crate) fn from_index(idx: usize) -> Option<Difficulty> {
+		match idx {
+			0 => Some(Difficulty::Easy),
+			1 => Some(Difficulty::Normal),
+			2 => Some(Difficulty::Hard),
+			3 => Some(Difficulty::Nightmare),
+			_ => None
+		}
+	}
+}
+
+// every config value is cut down to this, the rules add and multiply them with board sizes
+const MAX_VALUE: usize = 1_000_000;
+
+// Game balance, every number the rules use lives here.
+// Missing json fields keep the value of the difficulty preset (Normal when not given).
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameConfig {
+	// preset the values came from, changing single values by hand keeps it
+	pub difficulty: Difficulty,
+	pub snake_start_size: usize,
+	// steps left for the step bonus, and how many a reward adds
+	pub start_steps: usize,
+	pub reward_steps: usize,
+
+	// spawn timers are spawn_steps + random(side * spawn_range)
+	pub trap_spawn_steps: usize,
+	pub trap_spawn_range: usize,
+	pub trap_life_min: usize,
+	pub trap_life_range: usize,
+	// every n-th trap gives the bonus points, the others divide the points
+	pub trap_bonus_every: usize,
+	pub trap_points_divisor: usize,
+
+	pub super_bonus_spawn_steps: usize,
+	pub super_bonus_spawn_range: usize,
+	pub super_bonus_life: usize,
+	pub super_bonus_points: usize,
+	pub super_bonus_points_per_cell: usize,
+
+	pub enemy_spawn_steps: usize,
+	pub enemy_spawn_range: usize,
+	pub enemy_points: usize,
+	pub enemy_life: usize,
+	// extra steps the enemy waits between moves, 0 is the fastest
+	pub enemy_rest_steps: usize,
+	pub enemy_points_per_life: usize,
+	pub enemy_bite_points: usize,
+	pub enemy_bite_life: usize,
+	pub enemy_min_cut: usize,
+	pub enemy_reward_points: usize,
+	pub enemy_reward_life: usize,
+	pub enemy_bonus_points: usize,
+	pub enemy_bonus_life: usize,
+	pub enemy_trap_life: usize,
+	pub enemy_trap_death_life: usize,
+	// enemies out at once, starts at one and grows by one every enemy_ramp_steps (0 starts with all)
+	pub max_enemies: usize,
+	pub enemy_ramp_steps: usize,
+
+	// every n steps the snake dies if it ate less than one item per activity_steps_per_item, 0 turns it off
+	pub activity_check_steps: usize,
+	pub activity_steps_per_item: usize,
+
+	// base game speed, the snake speeds up from it as it grows
+	pub speed_ms: usize,
+
+	// the game ends once a snake has this many points, the most points win (0 plays on)
+	pub score_limit: usize
+}
+
+impl Default for GameConfig {
+	fn default() -> GameConfig {
+		GameConfig {
+			difficulty: Difficulty::Normal,
+			snake_start_size: crate::SNAKE_SPAWN_SIZE,
+			start_steps: 10,
+			reward_steps: 7,
+			trap_spawn_steps: 2,
+			trap_spawn_range: 1,
+			trap_life_min: 2,
+			trap_life_range: 10,
+			trap_bonus_every: 5,
+			trap_points_divisor: 2,
+			super_bonus_spawn_steps: 4,
+			super_bonus_spawn_range: 2,
+			super_bonus_life: 5,
+			super_bonus_points: 300,
+			super_bonus_points_per_cell: 10,
+			enemy_spawn_steps: 5,
+			enemy_spawn_range: 4,
+			enemy_points: 500,
+			enemy_life: 50,
+			enemy_rest_steps: 1,
+			enemy_points_per_life: 15,
+			enemy_bite_points: 1800,
+			enemy_bite_life: 35,
+			enemy_min_cut: 4,
+			enemy_reward_points: 150,
+			enemy_reward_life: 25,
+			enemy_bonus_points: 300,
+			enemy_bonus_life: 25,
+			enemy_trap_life: 15,
+			enemy_trap_death_life: 20,
+			max_enemies: 1,
+			enemy_ramp_steps: 0,
+			activity_check_steps: 100,
+			activity_steps_per_item: 20,
+			speed_ms: 1700,
+			score_limit: 0
+		}
+	}
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl GameConfig {
+	#[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+	pub fn new() -> GameConfig {
+		GameConfig::default()
+	}
+
+	pub fn with_difficulty(difficulty: Difficulty) -> GameConfig {
+		let normal = GameConfig::default();
+		match difficulty {
+			Difficulty::Easy => GameConfig {
+				difficulty,
+				trap_spawn_steps: 6,
+				trap_spawn_range: 2,
+				trap_bonus_every: 3,
+				super_bonus_spawn_range: 1,
+				super_bonus_life: 8,
+				enemy_spawn_steps: 20,
+				enemy_spawn_range: 6,
+				enemy_life: 30,
+				enemy_rest_steps: 2,
+				enemy_bite_points: 900,
+				enemy_bite_life: 20,
+				enemy_min_cut: 6,
+				activity_check_steps: 150,
+				activity_steps_per_item: 30,
+				speed_ms: 2000,
+				..normal
+			},
+			Difficulty::Normal => normal,
+			Difficulty::Hard => GameConfig {
+				difficulty,
+				trap_spawn_steps: 1,
+				trap_bonus_every: 7,
+				super_bonus_spawn_range: 3,
+				super_bonus_life: 4,
+				enemy_spawn_steps: 3,
+				enemy_spawn_range: 3,
+				enemy_life: 70,
+				enemy_rest_steps: 0,
+				enemy_bite_life: 45,
+				max_enemies: 2,
+				enemy_ramp_steps: 400,
+				activity_check_steps: 80,
+				activity_steps_per_item: 15,
+				speed_ms: 1500,
+				..normal
+			},
+			Difficulty::Nightmare => GameConfig {
+				difficulty,
+				trap_spawn_steps: 0,
+				trap_life_range: 14,
+				trap_bonus_every: 10,
+				trap_points_divisor: 3,
+				super_bonus_spawn_range: 4,
+				super_bonus_life: 3,
+				enemy_spawn_steps: 1,
+				enemy_spawn_range: 2,
+				enemy_life: 90,
+				enemy_rest_steps: 0,
+				enemy_bite_points: 2500,
+				enemy_bite_life: 50,
+				enemy_min_cut: 2,
+				max_enemies: 3,
+				enemy_ramp_steps: 250,
+				activity_check_steps: 60,
+				activity_steps_per_item: 12,
+				speed_ms: 1300,
+				..normal
+			}
+		}
+	}
+
+	// fields are laid over the preset named by "difficulty"
+	pub fn from_json(json: &str) -> Result<GameConfig, DecodeError> {
+		let json_err = |err: serde_json::Error| DecodeError::Json(err.to_string());
+		let value: serde_json::Value = serde_json::from_str(json).map_err(json_err)?;
+		let fields = value.as_object().ok_or_else(|| DecodeError::Json(String::from("game config must be an object")))?;
+		let difficulty: Difficulty = match fields.get("difficulty") {
+			Some(difficulty) => serde_json::from_value(difficulty.clone()).map_err(json_err)?,
+			None => Difficulty::Normal
+		};
+		let mut config = serde_json::to_value(GameConfig::with_difficulty(difficulty)).map_err(json_err)?;
+		if let Some(preset) = config.as_object_mut() {
+			for (key, value) in fields {
+				preset.insert(key.clone(), value.clone());
+			}
+		}
+		serde_json::from_value(config).map_err(json_err)
+	}
+
+	pub fn to_json(&self) -> String {
+		serde_json::to_string(self).unwrap_or_default()
+	}
+}
+
+impl GameConfig {
+	// values that would stall or crash the game are raised to the smallest working one, huge ones are capped
+	pub(crate) fn checked(mut self) -> GameConfig {
+		for value in self.values_mut() {
+			*value = (*value).min(MAX_VALUE);
+		}
+		self.snake_start_size = self.snake_start_size.max(2);
+		self.start_steps = self.start_steps.max(1);
+		self.reward_steps = self.reward_steps.max(1);
+		self.trap_life_min = self.trap_life_min.max(1);
+		self.trap_points_divisor = self.trap_points_divisor.max(1);
+		self.super_bonus_spawn_steps = self.super_bonus_spawn_steps.max(1);
+		self.super_bonus_life = self.super_bonus_life.max(1);
+		self.enemy_min_cut = self.enemy_min_cut.max(2);
+		self.speed_ms = self.speed_ms.max(1);
+		self
+	}
+
+	fn values(&self) -> [usize; 35] {
+		[
+			self.snake_start_size, self.start_steps, self.reward_steps,
+			self.trap_spawn_steps, self.trap_spawn_range, self.trap_life_min, self.trap_life_range,
+			self.trap_bonus_every, self.trap_points_divisor,
+			self.super_bonus_spawn_steps, self.super_bonus_spawn_range, self.super_bonus_life,
+			self.super_bonus_points, self.super_bonus_points_per_cell,
+			self.enemy_spawn_steps, self.enemy_spawn_range, self.enemy_points, self.enemy_life, self.enemy_rest_steps,
+			self.enemy_points_per_life,
+			self.enemy_bite_points, self.enemy_bite_life, self.enemy_min_cut,
+			self.enemy_reward_points, self.enemy_reward_life, self.enemy_bonus_points, self.enemy_bonus_life,
+			self.enemy_trap_life, self.enemy_trap_death_life, self.max_enemies, self.enemy_ramp_steps,
+			self.activity_check_steps, self.activity_steps_per_item,
+			self.speed_ms, self.score_limit
+		]
+	}
+
+	fn values_mut(&mut self) -> [&mut usize; 35] {
+		[
+			&mut self.snake_start_size, &mut self.start_steps, &mut self.reward_steps,
+			&mut self.trap_spawn_steps, &mut self.trap_spawn_range, &mut self.trap_life_min, &mut self.trap_life_range,
+			&mut self.trap_bonus_every, &mut self.trap_points_divisor,
+			&mut self.super_bonus_spawn_steps, &mut self.super_bonus_spawn_range, &mut self.super_bonus_life,
+			&mut self.super_bonus_points, &mut self.super_bonus_points_per_cell,
+			&mut self.enemy_spawn_steps, &mut self.enemy_spawn_range, &mut self.enemy_points, &mut self.enemy_life, &mut self.enemy_rest_steps,
+			&mut self.enemy_points_per_life,
+			&mut self.enemy_bite_points, &mut self.enemy_bite_life, &mut self.enemy_min_cut,
+			&mut self.enemy_reward_points, &mut self.enemy_reward_life, &mut self.enemy_bonus_points, &mut self.enemy_bonus_life,
+			&mut self.enemy_trap_life, &mut self.enemy_trap_death_life, &mut self.max_enemies, &mut self.enemy_ramp_steps,
+			&mut self.activity_check_steps, &mut self.activity_steps_per_item,
+			&mut self.speed_ms, &mut self.score_limit
+		]
+	}
+
+	// difficulty, then a counted list of values in field order, shorter lists keep the preset for the rest
+	pub(crate) fn write(&self, writer: &mut ByteWriter) {
+		writer.u8(self.difficulty as u8);
+		let values = self.values();
+		writer.varint(values.len());
+		for value in values {
+			writer.varint(value);
+		}
+	}
+
+	pub(crate) fn read(reader: &mut ByteReader) -> Result<GameConfig, DecodeError> {
+		let difficulty = Difficulty::from_index(reader.u8()? as usize).ok_or(DecodeError::Invalid("difficulty"))?;
+		let mut config = GameConfig::with_difficulty(difficulty);
+		let count = reader.list_len(1)?;
+		let mut slots = config.values_mut();
+		if count > slots.len() {
+			return Err(DecodeError::Invalid("game config"));
+		}
+		for slot in slots.iter_mut().take(count) {
+			**slot = reader.varint()?;
+		}
+		Ok(config.checked())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::WorldMap;
+
+	#[test]
+	fn json_is_laid_over_the_preset() {
+		let config = GameConfig::from_json("{\"difficulty\": \"Hard\", \"enemy_points\": 7}").ok().unwrap();
+		let hard = GameConfig::with_difficulty(Difficulty::Hard);
+		assert!(config.difficulty == Difficulty::Hard);
+		assert_eq!(config.enemy_points, 7);
+		assert_eq!(config.trap_spawn_range, hard.trap_spawn_range);
+		assert_eq!(config.speed_ms, hard.speed_ms);
+
+		assert!(GameConfig::from_json("{}").ok() == Some(GameConfig::default()));
+		let round_trip = GameConfig::from_json(&config.to_json()).ok();
+		assert!(round_trip == Some(config));
+	}
+
+	#[test]
+	fn bad_json_is_an_error() {
+		for json in ["[1, 2]", "{\"speed_ms\": -1}", "{\"difficulty\": \"Impossible\"}", "{\"enemy_life\": "] {
+			assert!(matches!(GameConfig::from_json(json), Err(DecodeError::Json(_))), "{}", json);
+		}
+	}
+
+	#[test]
+	fn stalling_values_are_raised() {
+		let config = GameConfig { start_steps: 0, reward_steps: 0, speed_ms: 0, snake_start_size: 0, ..GameConfig::default() };
+		let world = WorldMap::new_with_config_and_seed(10, 45, config, 1);
+		let checked = world.config();
+		assert_eq!((checked.start_steps, checked.reward_steps, checked.speed_ms, checked.snake_start_size), (1, 1, 1, 2));
+		assert_eq!(world.snake_length(), 2);
+	}
+
+	#[test]
+	fn presets_get_harder() {
+		let presets: Vec<GameConfig> = (0..4).map(|idx| GameConfig::with_difficulty(Difficulty::from_index(idx).unwrap())).collect();
+		assert!(presets[1] == GameConfig::default());
+		for pair in presets.windows(2) {
+			assert!(pair[0].speed_ms > pair[1].speed_ms);
+			assert!(pair[0].enemy_spawn_steps > pair[1].enemy_spawn_steps);
+			assert!(pair[0].enemy_life < pair[1].enemy_life);
+			assert!(pair[0].max_enemies <= pair[1].max_enemies);
+		}
+		assert!(Difficulty::from_index(4).is_none());
+	}
+
+	#[test]
+	fn games_keep_their_difficulty() {
+		let mut world = WorldMap::new_with_difficulty_and_seed(10, 45, Difficulty::Hard, 3);
+		assert!(world.config() == GameConfig::with_difficulty(Difficulty::Hard));
+		assert!(world.difficulty() == Difficulty::Hard);
+		assert_eq!(world.difficulty_text(), "Hard");
+		world.set_autopilot(true);
+		world.start_game();
+		for _ in 0..50 {
+			world.update();
+		}
+		assert!(world.get_game_stat().difficulty == Difficulty::Hard);
+		// the preset runs the rules, Hard ticks faster than Normal
+		let normal = WorldMap::new_with_seed(10, 45, 3);
+		assert!(world.tick_interval_ms() < normal.tick_interval_ms());
+	}
+
+	#[test]
+	fn huge_values_are_capped() {
+		let json = format!("{{\"trap_spawn_range\": {max}, \"super_bonus_points_per_cell\": {max}, \"enemy_spawn_range\": {max}}}", max = u64::MAX);
+		let config = GameConfig::from_json(&json).ok().unwrap();
+		let mut world = WorldMap::new_with_config_and_seed(10, 45, config, 1);
+		assert_eq!(world.config().trap_spawn_range, MAX_VALUE);
+		world.set_autopilot(true);
+		world.start_game();
+		for _ in 0..300 {
+			world.update();
+		}
+		assert!(world.super_bonus_points() >= MAX_VALUE);
+	}
+}
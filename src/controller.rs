@@ -0,0 +1,239 @@
+use std::collections::VecDeque;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{step_cell, BoundaryMode, Direction};
+
+// which controller steers a computer snake
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ControllerKind {
+	Greedy,
+	Survival
+}
+
+impl ControllerKind {
+	pub(crate) fn from_index(idx: usize) -> Option<ControllerKind> {
+		match idx {
+			0 => Some(ControllerKind::Greedy),
+			1 => Some(ControllerKind::Survival),
+			_ => None
+		}
+	}
+}
+
+// The board as one snake sees it right before it moves.
+pub struct SnakeView<'a> {
+	pub head: usize,
+	pub tail: usize,
+	pub direction: Direction,
+	pub length: usize,
+	pub width: usize,
+	pub height: usize,
+	pub boundary_mode: BoundaryMode,
+	pub portals: &'a [(usize, usize)],
+	// false on obstacles, snake bodies (its own too, but the tail), the trap and next to other snake heads
+	pub free: Vec<bool>,
+	pub reward: Option<usize>,
+	pub super_bonus: Option<usize>,
+	pub enemies: Vec<usize>
+}
+
+impl SnakeView<'_> {
+	// where the head lands, portals included, None behind a wall
+	pub fn next_cell(&self, idx: usize, direction: Direction) -> Option<usize> {
+		let next = step_cell(idx, direction, self.width, self.height, self.boundary_mode)?;
+		for (entry, exit) in self.portals {
+			if *entry == next {
+				return Some(*exit);
+			}
+			if *exit == next {
+				return Some(*entry);
+			}
+		}
+		Some(next)
+	}
+
+	pub fn is_food(&self, idx: usize) -> bool {
+		Some(idx) == self.reward || Some(idx) == self.super_bonus || self.enemies.contains(&idx)
+	}
+
+	// moves that don't kill the snake right away, the current direction first
+	pub fn safe_moves(&self) -> Vec<(Direction, usize)> {
+		let others = [Direction::Up, Direction::Right, Direction::Down, Direction::Left].into_iter()
+			.filter(|direction| *direction != self.direction);
+		std::iter::once(self.direction).chain(others)
+			.filter_map(|direction| self.next_cell(self.head, direction).filter(|next| self.free[*next]).map(|next| (direction, next)))
+			.collect()
+	}
+
+	// free cells reachable from idx, idx counts as one
+	pub fn room(&self, idx: usize) -> usize {
+		self.flood(idx, |_| false).0
+	}
+
+	// a snake that can still follow its own tail can't be shut in
+	pub fn reaches_tail(&self, idx: usize) -> bool {
+		self.flood(idx, |cell| cell == self.tail).1
+	}
+
+	// (cells filled, stop found), stops early when stop is true for a cell
+	fn flood(&self, idx: usize, stop: impl Fn(usize) -> bool) -> (usize, bool) {
+		let mut seen = vec![false; self.free.len()];
+		let mut queue = VecDeque::new();
+		seen[idx] = true;
+		queue.push_back(idx);
+		let mut count = 0;
+		while let Some(idx) = queue.pop_front() {
+			count += 1;
+			if stop(idx) {
+				return (count, true);
+			}
+			for direction in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+				if let Some(next) = self.next_cell(idx, direction).filter(|next| !seen[*next] && self.free[*next]) {
+					seen[next] = true;
+					queue.push_back(next);
+				}
+			}
+		}
+		(count, false)
+	}
+
+	// first step of the shortest safe path from the head to any target cell
+	pub fn first_step_towards(&self, is_target: impl Fn(usize) -> bool) -> Option<Direction> {
+		let mut first: Vec<Option<Direction>> = vec![None; self.free.len()];
+		let mut seen = vec![false; self.free.len()];
+		let mut queue = VecDeque::new();
+		seen[self.head] = true;
+		queue.push_back(self.head);
+		while let Some(idx) = queue.pop_front() {
+			for direction in [self.direction, Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+				let Some(next) = self.next_cell(idx, direction).filter(|next| !seen[*next] && self.free[*next]) else {
+					continue;
+				};
+				seen[next] = true;
+				first[next] = first[idx].or(Some(direction));
+				if is_target(next) {
+					return first[next];
+				}
+				queue.push_back(next);
+			}
+		}
+		None
+	}
+
+	// Shortest path to a target if the tail stays reachable from there, else any move that keeps
+	// the tail reachable (the current direction first), else the move with the most room.
+	pub fn careful_step(&self, is_target: impl Fn(usize) -> bool) -> Option<Direction> {
+		let mut moves = self.safe_moves();
+		if let Some(towards) = self.first_step_towards(is_target) {
+			if let Some(at) = moves.iter().position(|(direction, _)| *direction == towards) {
+				let target_move = moves.remove(at);
+				moves.insert(0, target_move);
+			}
+		}
+		if let Some((direction, _)) = moves.iter().find(|(_, next)| self.reaches_tail(*next)) {
+			return Some(*direction);
+		}
+		let mut best: Option<(Direction, usize)> = None;
+		for (direction, next) in moves {
+			let room = self.room(next);
+			if best.is_none_or(|(_, best_room)| room > best_room) {
+				best = Some((direction, room));
+			}
+		}
+		best.map(|(direction, _)| direction)
+	}
+}
+
+pub trait SnakeController {
+	// None keeps the current direction
+	fn next_direction(&self, view: &SnakeView) -> Option<Direction>;
+}
+
+// shortest path to the closest food or enemy, unless the snake would shut itself in there
+pub struct Greedy;
+
+impl SnakeController for Greedy {
+	fn next_direction(&self, view: &SnakeView) -> Option<Direction> {
+		let step = view.first_step_towards(|idx| view.is_food(idx))
+			.and_then(|direction| view.next_cell(view.head, direction).map(|next| (direction, next)));
+		match step {
+			Some((direction, next)) if view.room(next) >= view.length => Some(direction),
+			_ => Survival.next_direction(view)
+		}
+	}
+}
+
+// only takes moves after which it can still reach its tail, towards food when it can,
+// otherwise where the most room is left
+pub struct Survival;
+
+impl SnakeController for Survival {
+	fn next_direction(&self, view: &SnakeView) -> Option<Direction> {
+		view.careful_step(|idx| view.is_food(idx))
+	}
+}
+
+pub fn controller_for(kind: ControllerKind) -> &'static dyn SnakeController {
+	match kind {
+		ControllerKind::Greedy => &Greedy,
+		ControllerKind::Survival => &Survival
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// 5x3 walled board, the snake's head is on 7 heading left, cell 0 is a dead end behind obstacles 1, 10 and 11
+	fn pocket_view(length: usize) -> SnakeView<'static> {
+		let mut free = vec![true; 15];
+		for idx in [1, 10, 11, 7, 8, 9] {
+			free[idx] = false;
+		}
+		SnakeView {
+			head: 7,
+			tail: 4,
+			direction: Direction::Left,
+			length,
+			width: 5,
+			height: 3,
+			boundary_mode: BoundaryMode::Walls,
+			portals: &[],
+			free,
+			reward: Some(0),
+			super_bonus: None,
+			enemies: vec!()
+		}
+	}
+
+	#[test]
+	fn greedy_goes_for_the_food() {
+		let view = pocket_view(2);
+		assert!(controller_for(ControllerKind::Greedy).next_direction(&view) == Some(Direction::Left));
+		let view = SnakeView { reward: None, enemies: vec!(13), ..pocket_view(2) };
+		assert!(controller_for(ControllerKind::Greedy).next_direction(&view) == Some(Direction::Down));
+	}
+
+	#[test]
+	fn long_snakes_stay_out_of_dead_ends() {
+		let view = pocket_view(4);
+		assert_eq!(view.room(6), 3);
+		assert!(!view.reaches_tail(6));
+		assert!(controller_for(ControllerKind::Greedy).next_direction(&view) == Some(Direction::Up));
+		assert!(controller_for(ControllerKind::Survival).next_direction(&view) == Some(Direction::Up));
+	}
+
+	#[test]
+	fn moves_go_through_portals() {
+		let portals = [(6, 13)];
+		let view = SnakeView { portals: &portals, ..pocket_view(2) };
+		assert_eq!(view.next_cell(7, Direction::Left), Some(13));
+		assert_eq!(view.next_cell(4, Direction::Right), None);
+		let moves: Vec<Direction> = view.safe_moves().into_iter().map(|(direction, _)| direction).collect();
+		assert!(moves == vec!(Direction::Left, Direction::Up, Direction::Down));
+	}
+}
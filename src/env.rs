@@ -0,0 +1,236 @@
+use crate::{Direction, GameConfig, GameEvent, GameStat, GameStatus, Level, Reason, WorldMap};
+
+// Board as a channels x height x width tensor of 0.0 / 1.0, the cell index of the board is the index inside a plane.
+#[derive(Clone, PartialEq)]
+pub struct Observation {
+	pub width: usize,
+	pub height: usize,
+	pub data: Vec<f32>
+}
+
+impl Observation {
+	// channels, one width x height plane each
+	pub const HEAD: usize = 0;
+	pub const BODY: usize = 1;
+	pub const REWARD: usize = 2;
+	pub const TRAP: usize = 3;
+	pub const SUPER_BONUS: usize = 4;
+	pub const ENEMY: usize = 5;
+	// obstacles and the snakes of other players, everything the head must not run into
+	pub const WALL: usize = 6;
+	pub const CHANNELS: usize = 7;
+
+	pub fn get(&self, channel: usize, idx: usize) -> f32 {
+		self.data[channel * self.width * self.height + idx]
+	}
+
+	pub fn shape(&self) -> (usize, usize, usize) {
+		(Observation::CHANNELS, self.height, self.width)
+	}
+}
+
+pub struct StepInfo {
+	pub stat: GameStat,
+	pub status: Option<GameStatus>,
+	pub reason: Reason,
+	// the episode hit max_steps, done without the snake dying
+	pub truncated: bool,
+	pub events: Vec<GameEvent>
+}
+
+// Gym style wrapper, player 1 (index 0) is the agent. The reward of a step is the change of the points,
+// traps and enemy bites make it negative.
+pub struct Env {
+	level: Level,
+	config: GameConfig,
+	// episodes are cut after this many steps, 0 runs until the game ends
+	max_steps: usize,
+	world: WorldMap,
+	steps: usize
+}
+
+impl Env {
+	// the action space, an action is an index into it
+	pub const ACTIONS: [Direction; 4] = [Direction::Up, Direction::Right, Direction::Down, Direction::Left];
+
+	pub fn new(level: Level, config: GameConfig, max_steps: usize) -> Env {
+		let world = WorldMap::from_level_with_config(&level, config, Some(0));
+		Env {
+			level,
+			config,
+			max_steps,
+			world,
+			steps: 0
+		}
+	}
+
+	pub fn reset(&mut self, seed: u64) -> Observation {
+		self.restart(seed);
+		self.observation()
+	}
+
+	pub(crate) fn restart(&mut self, seed: u64) {
+		self.world = WorldMap::from_level_with_config(&self.level, self.config, Some(seed));
+		self.world.start_game();
+		self.steps = 0;
+	}
+
+	// action is an index into ACTIONS, anything else (or a reversal) keeps the snake going straight
+	pub fn step(&mut self, action: usize) -> (Observation, f32, bool, StepInfo) {
+		let (reward, over, truncated) = self.play(action);
+		let info = StepInfo {
+			stat: self.world.get_game_stat(),
+			status: self.world.game_status(),
+			reason: self.world.players[0].reason,
+			truncated,
+			events: self.world.drain_events()
+		};
+		(self.observation(), reward, over || truncated, info)
+	}
+
+	// (reward, game over, cut at max_steps), builds nothing so batches stay cheap
+	pub(crate) fn play(&mut self, action: usize) -> (f32, bool, bool) {
+		let points_before = self.world.points();
+		if let Some(direction) = Env::ACTIONS.get(action) {
+			self.world.change_snake_direction(*direction);
+		}
+		self.world.update();
+		self.steps += 1;
+		let over = self.world.game_status() != Some(GameStatus::Played);
+		let truncated = !over && self.max_steps != 0 && self.steps >= self.max_steps;
+		(self.world.points() as f32 - points_before as f32, over, truncated)
+	}
+
+	// batches don't read the events, they would only pile up
+	pub(crate) fn clear_events(&mut self) {
+		self.world.events.clear();
+	}
+
+	pub fn observation(&self) -> Observation {
+		let mut data = vec![0.0; Observation::CHANNELS * self.world.get_2d_size()];
+		self.write_observation(&mut data);
+		Observation {
+			width: self.world.width,
+			height: self.world.height,
+			data
+		}
+	}
+
+	// out has to hold Observation::CHANNELS * width * height values, it is overwritten
+	pub fn write_observation(&self, out: &mut [f32]) {
+		out.fill(0.0);
+		self.lit_values(|pos| out[pos] = 1.0);
+	}
+
+	// every position of the observation that is 1.0, in no particular order (a cell can come twice)
+	pub(crate) fn lit_values(&self, mut lit: impl FnMut(usize)) {
+		let world = &self.world;
+		let cells = world.get_2d_size();
+		let mut set = |channel: usize, idx: usize| lit(channel * cells + idx);
+		let body = &world.players[0].snake.body;
+		set(Observation::HEAD, body[0].0);
+		for cell in &body[1..] {
+			set(Observation::BODY, cell.0);
+		}
+		if let Some(reward) = &world.reward_cell {
+			set(Observation::REWARD, reward.idx);
+		}
+		if let Some(trap) = &world.trap_cell {
+			set(Observation::TRAP, trap.idx);
+		}
+		if let Some(bonus) = &world.super_bonus_cell {
+			set(Observation::SUPER_BONUS, bonus.0);
+		}
+		for enemy in &world.enemies {
+			set(Observation::ENEMY, enemy.idx);
+		}
+		for idx in &world.obstacles {
+			set(Observation::WALL, *idx);
+		}
+		for player in &world.players[1..] {
+			for cell in &player.snake.body {
+				set(Observation::WALL, cell.0);
+			}
+		}
+	}
+
+	pub fn observation_len(&self) -> usize {
+		Observation::CHANNELS * self.world.get_2d_size()
+	}
+
+	pub fn world(&self) -> &WorldMap {
+		&self.world
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{RewardCell, RewardType};
+
+	fn env(text: &str, max_steps: usize) -> Env {
+		Env::new(Level::parse(text).ok().unwrap(), GameConfig::default(), max_steps)
+	}
+
+	fn plane_sum(observation: &Observation, channel: usize) -> f32 {
+		(0..observation.width * observation.height).map(|idx| observation.get(channel, idx)).sum()
+	}
+
+	#[test]
+	fn reset_lays_out_the_board() {
+		let mut env = env("##......\n..>.....\n", 0);
+		let observation = env.reset(1);
+		assert_eq!(observation.shape(), (Observation::CHANNELS, 2, 8));
+		assert_eq!(observation.data.len(), env.observation_len());
+		assert_eq!(observation.get(Observation::HEAD, 10), 1.0);
+		assert_eq!(observation.get(Observation::BODY, 9), 1.0);
+		assert_eq!(plane_sum(&observation, Observation::HEAD), 1.0);
+		assert_eq!(plane_sum(&observation, Observation::REWARD), 1.0);
+		assert_eq!((observation.get(Observation::WALL, 0), observation.get(Observation::WALL, 1)), (1.0, 1.0));
+		assert_eq!(plane_sum(&observation, Observation::WALL), 2.0);
+		// same seed, same start
+		assert!(env.reset(1) == observation);
+	}
+
+	#[test]
+	fn step_rewards_the_points() {
+		let mut env = env("........\n..>.....\n", 0);
+		env.reset(1);
+		env.world.reward_cell = Some(RewardCell::new(11, RewardType::Yellow, 2));
+		let (observation, reward, done, info) = env.step(1);
+		assert!(!done);
+		assert_eq!(reward, info.stat.points as f32);
+		assert!(reward >= 2.0);
+		assert!(info.events.iter().any(|event| matches!(event, GameEvent::RewardEaten { idx: 11, .. })));
+		assert_eq!(observation.get(Observation::HEAD, 11), 1.0);
+		assert_eq!(observation.get(Observation::REWARD, 11), 0.0);
+		// nothing eaten, nothing earned
+		env.world.reward_cell = Some(RewardCell::new(0, RewardType::Yellow, 2));
+		assert_eq!(env.step(4).1, 0.0);
+	}
+
+	#[test]
+	fn death_ends_the_episode() {
+		let mut env = env("boundary: walls\n......>.\n........\n", 0);
+		env.reset(1);
+		assert!(!env.step(1).2);
+		let (_, _, done, info) = env.step(1);
+		assert!(done && !info.truncated);
+		assert!(info.reason == Reason::HitWall);
+		assert!(info.status == Some(GameStatus::Lost));
+	}
+
+	#[test]
+	fn long_episodes_are_cut() {
+		let mut env = env("........\n..>.....\n........\n", 3);
+		env.reset(1);
+		env.world.reward_cell = Some(RewardCell::new(20, RewardType::Yellow, 2));
+		assert!(!env.step(1).2);
+		assert!(!env.step(1).2);
+		let (_, _, done, info) = env.step(1);
+		assert!(done && info.truncated);
+		assert!(info.status == Some(GameStatus::Played));
+		env.reset(2);
+		assert!(!env.step(1).2);
+	}
+}
@@ -0,0 +1,34 @@
+use serde::Serialize;
+
+use crate::{GameStatus, Reason};
+
+// What happened during update(), in the order it happened.
+// JS gets them from drain_events_json as [{"type": "RewardEaten", "idx": 12, "points": 9}, ...]
+#[derive(Clone, PartialEq, Serialize)]
+#[serde(tag = "type")]
+pub enum GameEvent {
+	// player is the index of the snake, 0 in single player games
+	RewardEaten { player: usize, idx: usize, points: usize },
+	TrapSpawned { idx: usize },
+	// bonus is true when the trap paid out the bonus points instead of cutting the points
+	TrapTriggered { player: usize, idx: usize, bonus: bool },
+	// its life ran out before anything stepped on it
+	TrapExpired { idx: usize },
+	SuperBonusSpawned { idx: usize },
+	SuperBonusEaten { player: usize, idx: usize, points: usize },
+	SuperBonusExpired { idx: usize },
+	EnemySpawned { idx: usize },
+	// starved, life ran out
+	EnemyExpired { idx: usize },
+	EnemyEaten { player: usize, idx: usize, points: usize },
+	// snake length after the bite
+	EnemyBitSnake { player: usize, cut_at: usize },
+	EnemyAteReward { idx: usize },
+	EnemyAteSuperBonus { idx: usize },
+	EnemyHitTrap { idx: usize, died: bool },
+	// the weaker enemy was swallowed on this cell
+	EnemiesCollided { idx: usize },
+	SnakeDied { player: usize, reason: Reason },
+	// reason is player 1's, winner is None on a draw and when a single player lost
+	GameOver { status: GameStatus, reason: Reason, winner: Option<usize> }
+}
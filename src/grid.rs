@@ -0,0 +1,186 @@
+use crate::GameRng;
+
+// What lies on every cell of the board, WorldMap keeps it in step with its snakes and items
+// so collision and spawn checks don't have to walk the snake bodies.
+#[derive(Clone, Copy, Default)]
+struct Cell {
+	// snake segments, a snake that just grew holds its last cell twice and dead snakes stay on the board
+	snakes: u16,
+	// enemies can share a cell until one swallows the other
+	enemies: u16,
+	items: u8
+}
+
+impl Cell {
+	fn is_empty(&self) -> bool {
+		self.snakes == 0 && self.enemies == 0 && self.items == 0
+	}
+}
+
+// most snake segments (or enemies) one cell can count, a decoded file has to stay below it
+pub(crate) const MAX_PER_CELL: usize = u16::MAX as usize;
+
+pub(crate) const OBSTACLE: u8 = 1;
+pub(crate) const PORTAL: u8 = 2;
+pub(crate) const REWARD: u8 = 4;
+pub(crate) const TRAP: u8 = 8;
+pub(crate) const SUPER_BONUS: u8 = 16;
+// nothing spawns here and enemies walk around it
+pub(crate) const BLOCKED: u8 = OBSTACLE | PORTAL;
+
+pub(crate) struct Occupancy {
+	cells: Vec<Cell>,
+	// Fenwick tree counting the empty cells (1-based), finds the n-th empty cell in O(log n).
+	// The n-th in board order doesn't depend on the order cells were freed in, a loaded snapshot spawns the same way.
+	empty: Vec<usize>,
+	empty_count: usize
+}
+
+impl Occupancy {
+	pub fn new(size: usize) -> Occupancy {
+		// every cell empty, node i covers the lowest set bit of i cells
+		let empty = (0..=size).map(|i| i & i.wrapping_neg()).collect();
+		Occupancy {
+			cells: vec![Cell::default(); size],
+			empty,
+			empty_count: size
+		}
+	}
+
+	pub fn snakes(&self, idx: usize) -> usize {
+		self.cells[idx].snakes as usize
+	}
+
+	pub fn has_snake(&self, idx: usize) -> bool {
+		self.cells[idx].snakes != 0
+	}
+
+	pub fn add_snake(&mut self, idx: usize) {
+		self.change(idx, |cell| cell.snakes += 1);
+	}
+
+	pub fn remove_snake(&mut self, idx: usize) {
+		self.change(idx, |cell| cell.snakes -= 1);
+	}
+
+	pub fn add_enemy(&mut self, idx: usize) {
+		self.change(idx, |cell| cell.enemies += 1);
+	}
+
+	pub fn remove_enemy(&mut self, idx: usize) {
+		self.change(idx, |cell| cell.enemies -= 1);
+	}
+
+	// true when any of the item bits is on the cell
+	pub fn has(&self, idx: usize, items: u8) -> bool {
+		self.cells[idx].items & items != 0
+	}
+
+	pub fn set(&mut self, idx: usize, item: u8) {
+		self.change(idx, |cell| cell.items |= item);
+	}
+
+	pub fn unset(&mut self, idx: usize, item: u8) {
+		self.change(idx, |cell| cell.items &= !item);
+	}
+
+	// takes the item bit off every cell
+	pub fn clear(&mut self, item: u8) {
+		for idx in 0..self.cells.len() {
+			if self.has(idx, item) {
+				self.unset(idx, item);
+			}
+		}
+	}
+
+	// Uniform pick among the cells with nothing on them, one rnd call whatever the board looks like.
+	// None when there is no empty cell.
+	pub fn spawn_cell(&self, rng: &mut dyn GameRng) -> Option<usize> {
+		if self.empty_count == 0 {
+			return None;
+		}
+		Some(self.nth_empty(rng.rnd(self.empty_count)))
+	}
+
+	fn change(&mut self, idx: usize, update: impl FnOnce(&mut Cell)) {
+		let was_empty = self.cells[idx].is_empty();
+		update(&mut self.cells[idx]);
+		match (was_empty, self.cells[idx].is_empty()) {
+			(true, false) => {
+				self.empty_count -= 1;
+				self.add_empty(idx, false);
+			},
+			(false, true) => {
+				self.empty_count += 1;
+				self.add_empty(idx, true);
+			},
+			_ => {}
+		}
+	}
+
+	fn add_empty(&mut self, idx: usize, freed: bool) {
+		let mut i = idx + 1;
+		while i < self.empty.len() {
+			if freed {
+				self.empty[i] += 1;
+			} else {
+				self.empty[i] -= 1;
+			}
+			i += i & i.wrapping_neg();
+		}
+	}
+
+	// n counts from 0, walks down the tree from its biggest power of two
+	fn nth_empty(&self, n: usize) -> usize {
+		let size = self.cells.len();
+		let mut pos = 0;
+		let mut rest = n;
+		let mut step = if size == 0 { 0 } else { 1 << size.ilog2() };
+		while step != 0 {
+			if pos + step <= size && self.empty[pos + step] <= rest {
+				pos += step;
+				rest -= self.empty[pos];
+			}
+			step >>= 1;
+		}
+		pos
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::SeededRng;
+
+	#[test]
+	fn spawn_cell_on_a_full_board() {
+		let mut rng = SeededRng::new(1);
+		let mut grid = Occupancy::new(5);
+		for idx in 0..5 {
+			grid.add_snake(idx);
+		}
+		grid.set(2, REWARD);
+		assert_eq!(grid.spawn_cell(&mut rng), None);
+
+		grid.remove_snake(3);
+		assert_eq!(grid.spawn_cell(&mut rng), Some(3));
+		// still taken by the reward
+		grid.remove_snake(2);
+		assert_eq!(grid.spawn_cell(&mut rng), Some(3));
+	}
+
+	#[test]
+	fn spawn_cell_only_picks_empty_cells() {
+		let mut rng = SeededRng::new(2);
+		let mut grid = Occupancy::new(13);
+		for idx in [0, 4, 5, 9, 12] {
+			grid.set(idx, OBSTACLE);
+		}
+		grid.add_enemy(7);
+		for _ in 0..200 {
+			let idx = grid.spawn_cell(&mut rng).unwrap();
+			assert!(![0, 4, 5, 7, 9, 12].contains(&idx));
+		}
+		assert_eq!(Occupancy::new(0).spawn_cell(&mut rng), None);
+	}
+}
@@ -0,0 +1,261 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{step_cell, BoundaryMode, ControllerKind, Direction};
+
+// Board layout a game starts from: size, where the snakes spawn,
+// how the edges behave, the static obstacle cells and portal pairs.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Level {
+	pub width: usize,
+	pub height: usize,
+	pub snake_idx: usize,
+	// None keeps the classic spawn: body to the left of the head, moving up
+	#[serde(default)]
+	pub snake_direction: Option<Direction>,
+	#[serde(default)]
+	pub boundary_mode: BoundaryMode,
+	#[serde(default)]
+	pub obstacles: Vec<usize>,
+	#[serde(default)]
+	pub portals: Vec<(usize, usize)>,
+	// head and direction of player 2 and up, the first snake is snake_idx
+	#[serde(default)]
+	pub players: Vec<(usize, Direction)>,
+	// (player, controller) for the computer snakes, player 1 is 0
+	#[serde(default)]
+	pub controllers: Vec<(usize, ControllerKind)>
+}
+
+impl Level {
+	pub fn new(width: usize, height: usize, snake_idx: usize) -> Level {
+		Level {
+			width,
+			height,
+			snake_idx,
+			snake_direction: None,
+			boundary_mode: BoundaryMode::Wrap,
+			obstacles: vec!(),
+			portals: vec!(),
+			players: vec!(),
+			controllers: vec!()
+		}
+	}
+
+	// obstacles all along the border, the inner area is free
+	pub fn bordered(width: usize, height: usize, snake_idx: usize) -> Level {
+		let mut level = Level::new(width, height, snake_idx);
+		for idx in 0..width * height {
+			let row = idx / width;
+			let col = idx % width;
+			if row == 0 || col == 0 || row + 1 == height || col + 1 == width {
+				level.obstacles.push(idx);
+			}
+		}
+		level
+	}
+
+	// Plain text level, one character per cell:
+	//   .  floor
+	//   #  obstacle
+	//   P  portal, portals are paired in reading order
+	//   S or ^ > v <  snake head and the direction it moves, the body trails behind it,
+	//                 more of them are more players in reading order
+	// A grid without a spawn keeps the classic one (snake_direction None), the caller picks snake_idx.
+	// Lines before the grid can set options, "boundary: walls" or "boundary: wrap",
+	// "player 2: greedy" or "player 2: survival" lets the computer play that snake.
+	// Lines starting with ';' are comments.
+	pub fn parse(text: &str) -> Result<Level, LevelError> {
+		Level::parse_with_snake_size(text, crate::SNAKE_SPAWN_SIZE)
+	}
+
+	// every spawn needs floor for snake_size cells behind it, GameConfig::snake_start_size of the game
+	pub fn parse_with_snake_size(text: &str, snake_size: usize) -> Result<Level, LevelError> {
+		let mut boundary_mode = BoundaryMode::Wrap;
+		let mut rows: Vec<(usize, &str)> = vec!();
+		let mut controllers: Vec<(usize, usize, ControllerKind)> = vec!(); // (line, player, controller)
+		for (line_idx, raw_line) in text.lines().enumerate() {
+			let line_no = line_idx + 1;
+			let line = raw_line.trim_end();
+			if line.is_empty() || line.starts_with(';') {
+				continue;
+			}
+			if let Some((key, value)) = line.split_once(':') {
+				if !rows.is_empty() {
+					return Err(LevelError::new(line_no, 1, "options must come before the grid"));
+				}
+				let value_column = line.len() - value.trim_start().len() + 1;
+				match (key.trim(), value.trim()) {
+					("boundary", "walls") => boundary_mode = BoundaryMode::Walls,
+					("boundary", "wrap") => boundary_mode = BoundaryMode::Wrap,
+					("boundary", _) => return Err(LevelError::new(line_no, value_column, "boundary must be walls or wrap")),
+					(key, value) if key.starts_with("player ") => {
+						let player = match key["player ".len()..].trim().parse::<usize>() {
+							Ok(player) if player > 0 => player - 1,
+							_ => return Err(LevelError::new(line_no, 1, "players are counted from 1"))
+						};
+						let controller = match value {
+							"greedy" => ControllerKind::Greedy,
+							"survival" => ControllerKind::Survival,
+							_ => return Err(LevelError::new(line_no, value_column, "controller must be greedy or survival"))
+						};
+						controllers.push((line_no, player, controller));
+					},
+					_ => return Err(LevelError::new(line_no, 1, "unknown option"))
+				}
+				continue;
+			}
+			rows.push((line_no, line));
+		}
+
+		let (first_line, first_row) = *rows.first().ok_or_else(|| LevelError::new(1, 1, "level has no grid"))?;
+		let width = first_row.chars().count();
+		let height = rows.len();
+		let mut level = Level::new(width, height, 0);
+		level.boundary_mode = boundary_mode;
+		let mut spawns: Vec<(usize, Direction, usize, usize)> = vec!(); // (idx, direction, line, column)
+		let mut open_portal: Option<usize> = None;
+		let mut floor = vec![false; width * height];
+
+		for (row, (line_no, line)) in rows.iter().enumerate() {
+			let columns = line.chars().count();
+			if columns != width {
+				let msg = format!("row has {} cells, expected {} like line {}", columns, width, first_line);
+				return Err(LevelError::new(*line_no, columns.min(width) + 1, &msg));
+			}
+			for (col, glyph) in line.chars().enumerate() {
+				let idx = row * width + col;
+				let direction = match glyph {
+					'.' => {
+						floor[idx] = true;
+						continue;
+					},
+					'#' => {
+						level.obstacles.push(idx);
+						continue;
+					},
+					'P' => {
+						match open_portal.take() {
+							Some(entry) => level.portals.push((entry, idx)),
+							None => open_portal = Some(idx)
+						}
+						continue;
+					},
+					'S' | '^' => Direction::Up,
+					'>' => Direction::Right,
+					'v' => Direction::Down,
+					'<' => Direction::Left,
+					_ => return Err(LevelError::new(*line_no, col + 1, &format!("unknown cell '{}'", glyph)))
+				};
+				spawns.push((idx, direction, *line_no, col + 1));
+			}
+		}
+
+		if let Some(idx) = open_portal {
+			let (line_no, column) = position_of(&rows, width, idx);
+			return Err(LevelError::new(line_no, column, "portal without a pair"));
+		}
+		for (idx, direction, line_no, column) in &spawns {
+			let mut cell = *idx;
+			for _ in 1..snake_size {
+				match step_cell(cell, direction.opposite(), width, height, boundary_mode) {
+					Some(behind) if floor[behind] => cell = behind,
+					_ => return Err(LevelError::new(*line_no, *column, "no room for the snake body behind the spawn"))
+				}
+				floor[cell] = false; // taken, the next snake can't lie here
+			}
+		}
+		if let Some((idx, direction, _, _)) = spawns.first() {
+			level.snake_idx = *idx;
+			level.snake_direction = Some(*direction);
+			level.players = spawns[1..].iter().map(|(idx, direction, _, _)| (*idx, *direction)).collect();
+		}
+		for (line_no, player, controller) in controllers {
+			if player >= spawns.len() {
+				return Err(LevelError::new(line_no, 1, "no spawn for this player"));
+			}
+			level.controllers.retain(|(known, _)| *known != player);
+			level.controllers.push((player, controller));
+		}
+		Ok(level)
+	}
+}
+
+fn position_of(rows: &[(usize, &str)], width: usize, idx: usize) -> (usize, usize) {
+	(rows[idx / width].0, idx % width + 1)
+}
+
+// where a level file is broken, line and column start at 1
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelError {
+	pub line: usize,
+	pub column: usize,
+	pub message: String
+}
+
+impl LevelError {
+	fn new(line: usize, column: usize, message: &str) -> LevelError {
+		LevelError {
+			line,
+			column,
+			message: String::from(message)
+		}
+	}
+}
+
+impl fmt::Display for LevelError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+	}
+}
+
+impl std::error::Error for LevelError {}
+
+#[cfg(feature = "wasm")]
+impl From<LevelError> for wasm_bindgen::JsValue {
+	fn from(err: LevelError) -> wasm_bindgen::JsValue {
+		wasm_bindgen::JsError::new(&err.to_string()).into()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn error(text: &str) -> (usize, usize, String) {
+		let err = Level::parse(text).err().unwrap();
+		(err.line, err.column, err.message)
+	}
+
+	#[test]
+	fn parses_a_level() {
+		let level = Level::parse("boundary: walls\n; comment\n#..P\n..>P\n....\n").ok().unwrap();
+		assert_eq!((level.width, level.height), (4, 3));
+		assert!(level.boundary_mode == BoundaryMode::Walls);
+		assert_eq!(level.obstacles, [0]);
+		assert_eq!(level.portals, [(3, 7)]);
+		assert_eq!(level.snake_idx, 6);
+		assert!(level.snake_direction == Some(Direction::Right));
+	}
+
+	#[test]
+	fn errors_point_at_line_and_column() {
+		assert_eq!(error("....\n..x.\n"), (2, 3, String::from("unknown cell 'x'")));
+		assert_eq!(error("; ragged\n....\n...\n"), (3, 4, String::from("row has 3 cells, expected 4 like line 2")));
+		assert_eq!(error("boundary:  round\n....\n"), (1, 12, String::from("boundary must be walls or wrap")));
+		assert_eq!(error("....\nspeed: 3\n"), (2, 1, String::from("options must come before the grid")));
+		assert_eq!(error("....\n.P..\n"), (2, 2, String::from("portal without a pair")));
+		assert_eq!(error("boundary: walls\n.>..\n"), (2, 2, String::from("no room for the snake body behind the spawn")));
+		assert_eq!(error("; nothing\n"), (1, 1, String::from("level has no grid")));
+	}
+
+	#[test]
+	fn longer_snakes_need_more_room() {
+		let text = "boundary: walls\n#..>....\n";
+		assert!(Level::parse(text).is_ok());
+		let err = Level::parse_with_snake_size(text, 5).err().unwrap();
+		assert_eq!((err.line, err.column), (2, 4));
+		assert!(Level::parse_with_snake_size("..>.#...\n", 5).is_ok());
+	}
+}
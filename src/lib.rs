@@ -1,243 +1,814 @@
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
+#[cfg(feature = "wasm")]
 use wee_alloc::WeeAlloc;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+mod batch;
+mod behavior;
+mod body;
+mod codec;
+mod config;
+mod controller;
+mod env;
+mod events;
+mod grid;
+mod level;
+mod render;
+mod replay;
+mod rng;
+mod simulation;
+mod snapshot;
+pub use batch::WorldBatch;
+pub use behavior::{Coward, EnemyBehavior, EnemyView, Forager, Hunter, RandomWalk};
+pub use codec::DecodeError;
+pub use config::{Difficulty, GameConfig};
+pub use controller::{ControllerKind, Greedy, SnakeController, SnakeView, Survival};
+pub use env::{Env, Observation, StepInfo};
+pub use events::GameEvent;
+pub use level::{Level, LevelError};
+pub use render::{RenderRecord, RENDER_NONE, RENDER_STATE_VERSION};
+pub use replay::Replay;
+pub use rng::{GameRng, SeededRng};
+#[cfg(feature = "wasm")]
+pub use rng::JsRng;
+pub use simulation::{ScriptedInput, Simulation};
 
 // Memory optimizations
+#[cfg(feature = "wasm")]
 #[global_allocator]
 static ALLOC: WeeAlloc = WeeAlloc::INIT;
 
-#[wasm_bindgen(module = "/front/utils/rnd.js")]
-extern {
-	fn rnd(num: usize) -> usize;
-	fn now() -> usize;
-}
-
-
-#[wasm_bindgen]
+const SNAKE_SPAWN_SIZE: usize = 3;
+// (snake length up to, ticks per speed_ms x10), longer snakes use LONG_SNAKE_SPEED
+const SPEED_CURVE: [(usize, usize); 9] = [(6, 30), (9, 35), (12, 40), (15, 45), (18, 50), (25, 53), (30, 56), (35, 61), (40, 70)];
+const LONG_SNAKE_SPEED: usize = 48;
+// advance() drops time above this many ticks, a hidden tab shouldn't fast forward the game
+const MAX_CATCH_UP_TICKS: usize = 5;
+// key presses waiting for their tick, more are dropped
+const MAX_QUEUED_TURNS: usize = 3;
+// events nobody drains are dropped from the front past this
+const MAX_EVENTS: usize = 256;
+// bigger boards from set_size or a decoded file are rejected, they wouldn't fit in memory (or in the u32 cells of render_state)
+const MAX_CELLS: usize = 1 << 22;
+// rewards by RewardType and traps share these colors
+const ITEM_COLORS: [&str; 4] = ["#FFEAAE", "chocolate", "blueviolet", "brown"];
+
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub struct WorldMap {
-	size: usize,
-	snake: Snake,
-	next_cell: Option<SnakeCell>,
-	reward_cell: RewardCell,
+	width: usize,
+	height: usize,
+	// player 1 is players[0], single player games have only that one
+	players: Vec<Player>,
+	// None only while no cell is free for it
+	reward_cell: Option<RewardCell>,
 	status: Option<GameStatus>,
-	points: usize,
-	steps: usize,
-	bonus_points: usize,
+	winner: Option<usize>,
 	_steps: usize,
 	trap_steps: usize,
 	trap_cell: Option<TrapCell>,
 	life_steps: usize,
 	super_bonus_cell: Option<SuperBonus>,
 	super_bonus_steps: usize,
-	consumed_rewards: usize,
-	consumed_traps: usize,
-	consumed_super_bonuses: usize,
-	consumed_moving_targets: usize,
-	moving_cell: Option<MovingTarget>,
+	enemies: Vec<MovingTarget>,
 	steps_to_moving_target: usize,
-	reason: Reason,
-	eaten_by_enemy: usize,
-	max_snake_size:usize
+	boundary_mode: BoundaryMode,
+	obstacles: Vec<usize>,
+	portals: Vec<(usize, usize)>,
+	blocked_cells: Vec<usize>,
+	grid: grid::Occupancy,
+	config: GameConfig,
+	elapsed_ms: usize,
+	events: VecDeque<GameEvent>,
+	rng: Box<dyn GameRng>,
+	replay: Option<Replay>,
+	// the board was changed after the constructor, a seeded game starts over from its replay level
+	board_edited: bool,
+	// filled by render_state, not game state
+	render: Vec<RenderRecord>
 }
 
-#[wasm_bindgen]
 impl WorldMap {
-	pub fn new(size: usize, snake_idx: usize) -> WorldMap {
-		let snake_body_size: usize = 3;
-		let snake = Snake::new(snake_idx, snake_body_size);
-		let reward_cell: RewardCell = WorldMap::generate_reward_cell(size * size, &snake.body);
+	pub fn new_with_rng(width: usize, height: usize, snake_idx: usize, rng: Box<dyn GameRng>) -> WorldMap {
+		WorldMap::new_with_config_and_rng(width, height, snake_idx, GameConfig::default(), rng)
+	}
 
-		let trap_steps = now() % size + 2;
-		let super_bonus_steps = WorldMap::gen_super_bonus_steps(size);
-		let steps_to_moving_target = WorldMap::gen_moving_target_steps(size);
+	pub fn new_with_config_and_rng(width: usize, height: usize, snake_idx: usize, config: GameConfig, mut rng: Box<dyn GameRng>) -> WorldMap {
+		let config = config.checked();
+		let snake_body_size: usize = config.snake_start_size.min(width * height);
+		let snake = Snake::new(snake_idx, snake_body_size, width * height);
+		let mut grid = grid::Occupancy::new(width * height);
+		for cell in &snake.body {
+			grid.add_snake(cell.0);
+		}
+		let reward_cell = WorldMap::generate_reward_cell(&grid, snake.body.len(), rng.as_mut());
+		if let Some(reward) = &reward_cell {
+			grid.set(reward.idx, grid::REWARD);
+		}
+
+		let side = (width + height) / 2;
+		let trap_steps = rng.rnd(side.saturating_mul(config.trap_spawn_range)).saturating_add(config.trap_spawn_steps);
+		let super_bonus_steps = WorldMap::gen_super_bonus_steps(side, &config, rng.as_mut());
+		let steps_to_moving_target = WorldMap::gen_moving_target_steps(side, &config, rng.as_mut());
 
 		WorldMap {
-			size,
-			snake,
-			next_cell: Option::None,
+			width,
+			height,
+			players: vec![Player::new(snake, &config)],
 			reward_cell,
 			status: Option::None,
-			points: 0,
-			steps: 10,
-			bonus_points: 0,
-			_steps: 7,
+			winner: None,
+			_steps: config.reward_steps,
 			trap_steps,
 			trap_cell: Option::None,
 			life_steps: 0,
 			super_bonus_cell: Option::None,
 			super_bonus_steps,
-			consumed_rewards: 0,
-			consumed_traps: 0,
-			consumed_super_bonuses: 0,
-			consumed_moving_targets: 0,
-			moving_cell: Option::None,
+			enemies: vec!(),
 			steps_to_moving_target,
-			reason:Reason::StillAlive,
-			eaten_by_enemy: 0,
-			max_snake_size: snake_body_size
+			boundary_mode: BoundaryMode::Wrap,
+			obstacles: vec!(),
+			portals: vec!(),
+			blocked_cells: vec!(),
+			grid,
+			config,
+			elapsed_ms: 0,
+			events: VecDeque::new(),
+			rng,
+			replay: None,
+			board_edited: false,
+			render: vec!()
+		}
+	}
+
+	pub fn from_level(level: &Level) -> WorldMap {
+		WorldMap::from_level_with_config(level, GameConfig::default(), None)
+	}
+
+	pub fn from_level_with_seed(level: &Level, seed: u64) -> WorldMap {
+		WorldMap::from_level_with_config(level, GameConfig::default(), Some(seed))
+	}
+
+	// every constructor ends up here, a seed makes the game replayable
+	pub fn from_level_with_config(level: &Level, config: GameConfig, seed: Option<u64>) -> WorldMap {
+		let rng: Box<dyn GameRng> = match seed {
+			Some(seed) => Box::new(SeededRng::new(seed)),
+			None => rng::default_rng()
+		};
+		let mut world_map = WorldMap::new_with_config_and_rng(level.width, level.height, level.snake_idx, config, rng);
+		if let Some(seed) = seed {
+			let mut replay = Replay::new(seed, Level::new(level.width, level.height, level.snake_idx));
+			replay.config = world_map.config;
+			world_map.replay = Some(replay);
+		}
+		world_map.apply_level(level);
+		world_map.board_edited = false;
+		world_map
+	}
+
+	// the snakes come before the obstacles (those under a snake are dropped), their bodies already keep off them
+	fn apply_level(&mut self, level: &Level) {
+		self.set_boundary_mode(level.boundary_mode);
+		if let Some(direction) = level.snake_direction {
+			self.place_snake(level.snake_idx, direction, &level.obstacles);
+		}
+		for (idx, direction) in &level.players {
+			self.spawn_player(*idx, *direction, &level.obstacles);
+		}
+		for (p, controller) in &level.controllers {
+			self.set_controller(*p, Some(*controller));
 		}
+		self.set_obstacles(level.obstacles.clone());
+		self.set_portal_pairs(level.portals.clone());
 	}
 
-	fn gen_moving_target_steps(size: usize) -> usize {
-		rnd(size * 4) + 5
+	// Edits before the start drew from the rng in an order the replay can't repeat (a resized board,
+	// a reward moved off a new obstacle...), so the game begins from the replay level like Replay::run does.
+	// Queued turns and autopilots are kept, nothing else has happened yet.
+	fn restart_from_replay(&mut self) {
+		let Some(replay) = self.replay.take() else {
+			return;
+		};
+		let mut world = WorldMap::from_level_with_config(&replay.level, replay.config, Some(replay.seed));
+		for (player, old) in world.players.iter_mut().zip(&mut self.players) {
+			player.autopilot = old.autopilot;
+			player.turns = std::mem::take(&mut old.turns);
+		}
+		world.elapsed_ms = self.elapsed_ms;
+		world.events = std::mem::take(&mut self.events);
+		world.replay = Some(replay);
+		*self = world;
+	}
+
+	// Head on idx, body trailing behind it. A wall or an obstacle stops the body,
+	// the rest of it waits on the last cell (Level::parse_with_snake_size makes sure levels have room).
+	fn snake_behind(&self, idx: usize, direction: Direction, size: usize, obstacles: &[usize]) -> Snake {
+		let mut body = vec![SnakeCell(idx)];
+		for _ in 1..size {
+			let last = body[body.len() - 1].0;
+			let behind = step_cell(last, direction.opposite(), self.width, self.height, self.boundary_mode)
+				.filter(|behind| !obstacles.contains(behind))
+				.unwrap_or(last);
+			body.push(SnakeCell(behind));
+		}
+		Snake { body: body::SnakeBody::new(&body), direction }
+	}
+
+	fn place_snake(&mut self, idx: usize, direction: Direction, obstacles: &[usize]) {
+		let snake = self.snake_behind(idx, direction, self.snake_length(), obstacles);
+		for cell in &self.players[0].snake.body {
+			self.grid.remove_snake(cell.0);
+		}
+		for cell in &snake.body {
+			self.grid.add_snake(cell.0);
+		}
+		self.players[0].snake = snake;
+		self.players[0].turns.clear();
+		if let Some(replay) = &mut self.replay {
+			replay.level.snake_direction = Some(direction);
+		}
+		self.move_reward_off_snakes();
+	}
+
+	fn move_reward_off_snakes(&mut self) {
+		if self.reward_cell.as_ref().is_some_and(|reward| self.is_snake_cell(reward.idx)) {
+			self.respawn_reward();
+		}
+	}
+
+	// dead snakes stay on the board
+	fn is_snake_cell(&self, idx: usize) -> bool {
+		self.grid.has_snake(idx)
+	}
+
+	// from scratch for loaded games and resized boards, every change after that updates it in place
+	pub(crate) fn rebuild_grid(&mut self) {
+		let mut occupancy = grid::Occupancy::new(self.get_2d_size());
+		for cell in self.players.iter().flat_map(|player| &player.snake.body) {
+			occupancy.add_snake(cell.0);
+		}
+		for enemy in &self.enemies {
+			occupancy.add_enemy(enemy.idx);
+		}
+		for idx in &self.obstacles {
+			occupancy.set(*idx, grid::OBSTACLE);
+		}
+		for (entry, exit) in &self.portals {
+			occupancy.set(*entry, grid::PORTAL);
+			occupancy.set(*exit, grid::PORTAL);
+		}
+		if let Some(reward) = &self.reward_cell {
+			occupancy.set(reward.idx, grid::REWARD);
+		}
+		if let Some(trap) = &self.trap_cell {
+			occupancy.set(trap.idx, grid::TRAP);
+		}
+		if let Some(bonus) = &self.super_bonus_cell {
+			occupancy.set(bonus.0, grid::SUPER_BONUS);
+		}
+		self.grid = occupancy;
 	}
 
-	fn generate_moving_target(max: usize, snake_body: &Vec<SnakeCell>) -> MovingTarget {
-		let mut cell_idx: usize;
-		loop { 
-			cell_idx = rnd(max);
-			if !snake_body.contains(&SnakeCell(cell_idx)) {
-				break;
+	fn snake_cells_total(&self) -> usize {
+		self.players.iter().map(|player| player.snake.body.len()).sum()
+	}
+
+	// items and speed follow the longest snake
+	fn longest_snake(&self) -> usize {
+		self.players.iter().map(|player| player.snake.body.len()).max().unwrap_or(0)
+	}
+
+	fn alive_players(&self) -> Vec<usize> {
+		(0..self.players.len()).filter(|p| self.players[*p].alive()).collect()
+	}
+
+	// the board as snake p sees it, for the controllers
+	pub(crate) fn snake_view(&self, p: usize) -> SnakeView<'_> {
+		let mut free: Vec<bool> = (0..self.get_2d_size())
+			.map(|idx| !self.grid.has_snake(idx) && !self.grid.has(idx, grid::OBSTACLE))
+			.collect();
+		// the own tail moves on, unless the snake just grew and the last cell is doubled
+		let body = &self.players[p].snake.body;
+		let tail = body[body.len() - 1];
+		if self.grid.snakes(tail.0) == 1 {
+			free[tail.0] = true;
+		}
+		if let Some(trap) = &self.trap_cell {
+			free[trap.idx] = false;
+		}
+		// where another head can be next tick, running into it there kills both
+		for q in self.alive_players().into_iter().filter(|q| *q != p) {
+			for direction in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+				if let Some(next) = self.generate_next_snake_cell(q, &direction) {
+					free[next.0] = false;
+				}
 			}
 		}
-		MovingTarget::new(cell_idx)
+		let player = &self.players[p];
+		SnakeView {
+			head: player.head(),
+			tail: tail.0,
+			direction: player.snake.direction,
+			length: player.snake.body.len(),
+			width: self.width,
+			height: self.height,
+			boundary_mode: self.boundary_mode,
+			portals: &self.portals,
+			free,
+			reward: self.reward_cell_idx(),
+			super_bonus: self.super_bonus_cell.as_ref().map(|bonus| bonus.0),
+			enemies: self.enemy_cells()
+		}
+	}
+
+	// computer snakes choose their turn right before they move
+	fn steer_bots(&mut self) {
+		for p in self.alive_players() {
+			let Some(kind) = self.players[p].controller else {
+				continue;
+			};
+			let direction = controller::controller_for(kind).next_direction(&self.snake_view(p));
+			let turns = &mut self.players[p].turns;
+			turns.clear();
+			turns.extend(direction);
+		}
+	}
+
+	// only before the game starts, a replay couldn't play the change again
+	pub fn set_portal_pairs(&mut self, pairs: Vec<(usize, usize)>) -> bool {
+		if self.status.is_some() {
+			return false;
+		}
+		let max = self.get_2d_size();
+		let usable = |idx: usize| idx < max && !self.is_obstacle(idx) && !self.is_snake_cell(idx);
+		let mut portals: Vec<(usize, usize)> = vec!();
+		for (entry, exit) in pairs {
+			let taken = portals.iter().any(|pair| [pair.0, pair.1].contains(&entry) || [pair.0, pair.1].contains(&exit));
+			if entry != exit && usable(entry) && usable(exit) && !taken {
+				portals.push((entry, exit));
+			}
+		}
+		self.portals = portals;
+		self.board_edited = true;
+		if let Some(replay) = &mut self.replay {
+			replay.level.portals = self.portals.clone();
+		}
+		self.refresh_blocked_cells();
+		true
+	}
+
+	// nothing spawns on obstacles or portals, and the enemy walks around them
+	fn refresh_blocked_cells(&mut self) {
+		self.blocked_cells = blocked_cells(&self.obstacles, &self.portals);
+		self.grid.clear(grid::BLOCKED);
+		for idx in &self.obstacles {
+			self.grid.set(*idx, grid::OBSTACLE);
+		}
+		for (entry, exit) in &self.portals {
+			self.grid.set(*entry, grid::PORTAL);
+			self.grid.set(*exit, grid::PORTAL);
+		}
+
+		if self.reward_cell.as_ref().is_some_and(|reward| self.is_blocked(reward.idx)) {
+			self.respawn_reward();
+		}
+		if self.trap_cell.as_ref().is_some_and(|trap| self.is_blocked(trap.idx)) {
+			self.clear_trap_cell();
+		}
+		if self.super_bonus_cell.as_ref().is_some_and(|bonus| self.is_blocked(bonus.0)) {
+			self.set_super_bonus(None);
+		}
+		while let Some(n) = self.enemies.iter().position(|enemy| self.is_blocked(enemy.idx)) {
+			self.remove_moving_target(n);
+		}
+	}
+
+	fn is_blocked(&self, idx: usize) -> bool {
+		self.grid.has(idx, grid::BLOCKED)
+	}
+
+	// stepping on a portal moves you to its pair
+	fn portal_exit(&self, idx: usize) -> usize {
+		for (entry, exit) in &self.portals {
+			if *entry == idx {
+				return *exit;
+			}
+			if *exit == idx {
+				return *entry;
+			}
+		}
+		idx
+	}
+
+	// everything that happened since the last drain
+	pub fn drain_events(&mut self) -> Vec<GameEvent> {
+		self.events.drain(..).collect()
+	}
+
+	fn emit(&mut self, event: GameEvent) {
+		if self.events.len() >= MAX_EVENTS {
+			self.events.pop_front();
+		}
+		self.events.push_back(event);
+	}
+
+	// None for games without a seed, they can't be replayed
+	pub fn replay(&self) -> Option<Replay> {
+		let mut replay = self.replay.clone()?;
+		replay.ticks = self.life_steps;
+		Some(replay)
+	}
+
+	// what render_state shares with JS, header first
+	pub fn render_records(&mut self) -> &[RenderRecord] {
+		let mut records = std::mem::take(&mut self.render);
+		render::fill(self, &mut records);
+		self.render = records;
+		&self.render
+	}
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl WorldMap {
+	pub fn new(size: usize, snake_idx: usize) -> WorldMap {
+		WorldMap::new_rect(size, size, snake_idx)
+	}
+
+	// same seed and same inputs always give the same game
+	pub fn new_with_seed(size: usize, snake_idx: usize, seed: u64) -> WorldMap {
+		WorldMap::new_rect_with_seed(size, size, snake_idx, seed)
+	}
+
+	pub fn new_rect(width: usize, height: usize, snake_idx: usize) -> WorldMap {
+		WorldMap::from_level(&Level::new(width, height, snake_idx))
+	}
+
+	pub fn new_rect_with_seed(width: usize, height: usize, snake_idx: usize, seed: u64) -> WorldMap {
+		WorldMap::from_level_with_seed(&Level::new(width, height, snake_idx), seed)
+	}
+
+	pub fn new_with_config(size: usize, snake_idx: usize, config: GameConfig) -> WorldMap {
+		WorldMap::from_level_with_config(&Level::new(size, size, snake_idx), config, None)
+	}
+
+	pub fn new_with_config_and_seed(size: usize, snake_idx: usize, config: GameConfig, seed: u64) -> WorldMap {
+		WorldMap::from_level_with_config(&Level::new(size, size, snake_idx), config, Some(seed))
+	}
+
+	pub fn new_with_difficulty(size: usize, snake_idx: usize, difficulty: Difficulty) -> WorldMap {
+		WorldMap::new_with_config(size, snake_idx, GameConfig::with_difficulty(difficulty))
+	}
+
+	pub fn new_with_difficulty_and_seed(size: usize, snake_idx: usize, difficulty: Difficulty, seed: u64) -> WorldMap {
+		WorldMap::new_with_config_and_seed(size, snake_idx, GameConfig::with_difficulty(difficulty), seed)
+	}
+
+	pub fn config(&self) -> GameConfig {
+		self.config
+	}
+
+	pub fn difficulty(&self) -> Difficulty {
+		self.config.difficulty
+	}
+
+	pub fn difficulty_text(&self) -> String {
+		match self.config.difficulty {
+			Difficulty::Easy => String::from("Easy"),
+			Difficulty::Normal => String::from("Normal"),
+			Difficulty::Hard => String::from("Hard"),
+			Difficulty::Nightmare => String::from("Nightmare")
+		}
+	}
+
+	// drain_events as a json array, see GameEvent
+	pub fn drain_events_json(&mut self) -> String {
+		serde_json::to_string(&self.drain_events()).unwrap_or_default()
+	}
+
+	pub fn replay_bytes(&self) -> Option<Vec<u8>> {
+		self.replay().map(|replay| replay.to_bytes())
+	}
+
+	pub fn replay_json(&self) -> Option<String> {
+		self.replay().map(|replay| replay.to_json())
+	}
+
+	// versioned binary save, can be kept in localStorage and resumed with from_snapshot
+	pub fn to_snapshot(&self) -> Vec<u8> {
+		snapshot::encode(self)
+	}
+
+	pub fn from_snapshot(bytes: &[u8]) -> Result<WorldMap, DecodeError> {
+		snapshot::decode(bytes)
+	}
+
+	// see Level::parse for the format
+	pub fn from_level_str(text: &str) -> Result<WorldMap, LevelError> {
+		Ok(WorldMap::from_level(&Level::parse(text)?))
+	}
+
+	// for a level without a spawn, the snake starts on snake_idx like in WorldMap.new (a spawn in the level wins)
+	pub fn from_level_str_with_spawn(text: &str, snake_idx: usize) -> Result<WorldMap, LevelError> {
+		let mut level = Level::parse(text)?;
+		if level.snake_direction.is_none() {
+			level.snake_idx = snake_idx % (level.width * level.height);
+		}
+		Ok(WorldMap::from_level(&level))
+	}
+
+	pub fn from_level_str_with_seed(text: &str, seed: u64) -> Result<WorldMap, LevelError> {
+		Ok(WorldMap::from_level_with_seed(&Level::parse(text)?, seed))
+	}
+
+	pub fn from_level_str_with_config(text: &str, config: GameConfig) -> Result<WorldMap, LevelError> {
+		Ok(WorldMap::from_level_with_config(&Level::parse_with_snake_size(text, config.checked().snake_start_size)?, config, None))
+	}
+
+	fn gen_moving_target_steps(size: usize, config: &GameConfig, rng: &mut dyn GameRng) -> usize {
+		rng.rnd(size.saturating_mul(config.enemy_spawn_range)).saturating_add(config.enemy_spawn_steps)
+	}
+
+	fn generate_moving_target(grid: &grid::Occupancy, config: &GameConfig, rng: &mut dyn GameRng) -> Option<MovingTarget> {
+		let cell_idx = grid.spawn_cell(rng)?;
+		Some(MovingTarget::new(cell_idx, config, rng))
 	}
 
 	pub fn steps_to_moving_target(&self) -> usize { // test
 		self.steps_to_moving_target
 	}
 
+	// the moving_target_* getters describe the oldest enemy, enemy_buffer has all of them
 	pub fn moving_target_life(&self) -> usize {
-		if let Some(moving_target) = &self.moving_cell {
+		if let Some(moving_target) = self.enemies.first() {
 			moving_target.life
 		} else {
 			0
 		}
 	}
 	pub fn moving_target_points(&self) -> usize {
-		match &self.moving_cell {
+		match self.enemies.first() {
 			None => 0,
-			Some(moving_target) => moving_target.calculate_points()
+			Some(moving_target) => moving_target.calculate_points(self.config.enemy_points_per_life)
 		}
 	}
 
-	pub fn moving_target_cell_idx(&self) -> usize  {
-		match &self.moving_cell {
-			None => 1000000,
-			Some(moving_target) => moving_target.position()
-		}
+	// undefined in JS while there is no enemy
+	pub fn moving_target_cell_idx(&self) -> Option<usize> {
+		self.enemies.first().map(|moving_target| moving_target.position())
 	}
 
 	pub fn moving_target_status(&self) -> String {
-		match &self.moving_cell {
+		match self.enemies.first() {
 			None => String::from("Not exists"),
 			Some(moving_target) => moving_target.status_verbal()
 		}
 	}
 
-	fn remove_moving_target(&mut self) {
-		self.moving_cell = None;
-		self.steps_to_moving_target = WorldMap::gen_moving_target_steps(self.size);
+	pub fn enemy_count(&self) -> usize {
+		self.enemies.len()
+	}
+
+	// 4 numbers per enemy: cell, life, status (TargetStatus), points
+	pub fn enemy_buffer(&self) -> Vec<usize> {
+		self.enemies.iter()
+			.flat_map(|enemy| [enemy.idx, enemy.life, enemy.status as usize, enemy.calculate_points(self.config.enemy_points_per_life)])
+			.collect()
 	}
 
-	fn consume_moving_target(&mut self) {
-		self.snake.body.push(SnakeCell(self.snake.body[1].0));
-		let points = self.moving_target_points();
-		self.points += points;
-		self.bonus_points += points;
-		self.consumed_moving_targets += 1;
-		self.remove_moving_target();
+	pub fn enemy_status_text(&self, n: usize) -> String {
+		match self.enemies.get(n) {
+			None => String::from("Not exists"),
+			Some(enemy) => enemy.status_verbal()
+		}
 	}
 
-	fn increase_moving_cell_points(&mut self, points: usize) {
-		if let Some(moving_target) = &mut self.moving_cell {
-			moving_target.points += points;
+	// how many enemies may be out at once, grows by one every enemy_ramp_steps
+	fn allowed_enemies(&self) -> usize {
+		match self.config.enemy_ramp_steps {
+			0 => self.config.max_enemies,
+			ramp => self.config.max_enemies.min(1 + self.life_steps / ramp)
 		}
 	}
 
+	fn enemy_at(&self, idx: usize) -> Option<usize> {
+		self.enemies.iter().position(|enemy| enemy.idx == idx)
+	}
+
+	fn enemy_cells(&self) -> Vec<usize> {
+		self.enemies.iter().map(|enemy| enemy.idx).collect()
+	}
+
+	fn remove_moving_target(&mut self, n: usize) {
+		let enemy = self.enemies.remove(n);
+		self.grid.remove_enemy(enemy.idx);
+		// a running spawn countdown keeps going
+		if self.steps_to_moving_target == 0 {
+			self.steps_to_moving_target = WorldMap::gen_moving_target_steps(self.side(), &self.config, self.rng.as_mut());
+		}
+	}
+
+	fn consume_moving_target(&mut self, p: usize, n: usize) {
+		let player = &mut self.players[p];
+		let idx = self.enemies[n].idx;
+		let points = self.enemies[n].calculate_points(self.config.enemy_points_per_life);
+		player.points += points;
+		player.bonus_points += points;
+		player.consumed_moving_targets += 1;
+		self.emit(GameEvent::EnemyEaten { player: p, idx, points });
+		self.remove_moving_target(n);
+	}
+
 	fn check_max_poinst(&mut self) {
-		if self.snake_length() > self.max_snake_size {
-			self.max_snake_size = self.snake_length();
-		}
-	}
-
-	fn moving_cell_bite_snake(&mut self) {
-		self.points = 0;
-		self.eaten_by_enemy += 1;
-		self.increase_moving_cell_points(1800);
-		if let Some(moving_target) = &mut self.moving_cell {
-			let cut_index: usize = moving_target.idx;
-			if let Some(snake_cell) = WorldMap::find_snake_cell_index(&self.snake.body, cut_index) {
-				moving_target.life += 35;
-				let snake_cut_index: usize;
-				if snake_cell > 4 {
-					snake_cut_index = snake_cell;
-				} else {
-					snake_cut_index = 4;
+		for player in &mut self.players {
+			if player.snake.body.len() > player.max_snake_size {
+				player.max_snake_size = player.snake.body.len();
+			}
+		}
+	}
+
+	fn moving_cell_bite_snake(&mut self, p: usize, n: usize) {
+		let player = &mut self.players[p];
+		player.points = 0;
+		player.eaten_by_enemy += 1;
+		let min_cut = self.config.enemy_min_cut;
+		let moving_target = &mut self.enemies[n];
+		moving_target.points += self.config.enemy_bite_points;
+		let cut_index: usize = moving_target.idx;
+		if let Some(snake_cell) = WorldMap::find_snake_cell_index(&player.snake.body, cut_index) {
+			moving_target.life += self.config.enemy_bite_life;
+			let snake_cut_index: usize = if snake_cell > min_cut {
+				snake_cell
+			} else {
+				min_cut
+			};
+			if player.snake.body.len() > min_cut {
+				for cell in &player.snake.body[snake_cut_index..] {
+					self.grid.remove_snake(cell.0);
 				}
-				if self.snake_length() > 4 {
-					let (first_half, _) = self.snake.body.split_at_mut(snake_cut_index);
-					self.snake.body = first_half.to_vec();
-				} else {
-					self.reason = Reason::Eaten;
-					self.status = Some(GameStatus::Lost);
+				player.snake.body.truncate(snake_cut_index);
+			} else {
+				self.kill(p, Reason::Eaten);
+			}
+			self.emit(GameEvent::EnemyBitSnake { player: p, cut_at: self.players[p].snake.body.len() });
+		}
+	}
+
+	// one bite per snake that kills it is enough
+	fn check_enemies_bite_snake(&mut self, movers: &[usize]) {
+		for &p in movers {
+			for n in 0..self.enemies.len() {
+				let idx = self.enemies[n].idx;
+				if self.grid.has_snake(idx) && self.players[p].snake.body[1..].contains(&SnakeCell(idx)) {
+					self.moving_cell_bite_snake(p, n);
+					if !self.players[p].alive() {
+						break;
+					}
 				}
 			}
 		}
 	}
 
+	// when enemies share a food cell the hungriest one gets it
+	fn hungriest_enemy_at(&self, idx: usize) -> Option<usize> {
+		self.enemies.iter().enumerate()
+			.filter(|(_, enemy)| enemy.idx == idx)
+			.min_by_key(|(_, enemy)| enemy.life)
+			.map(|(n, _)| n)
+	}
+
 	fn check_moving_target_consume_trap(&mut self) {
-		let trap_idx = self.trap_cell_idx();
-		if let Some(moving_target) = &mut self.moving_cell {
-			if moving_target.idx == trap_idx {
-				if moving_target.life < 20 {
-					self.moving_cell = None;
-				} else {
-					moving_target.points = 0;
-					moving_target.life = 15;
-				}
-				self.recreate_trap_cell();
+		let Some(trap_idx) = self.trap_cell_idx() else {
+			return;
+		};
+		if let Some(n) = self.enemy_at(trap_idx) {
+			let moving_target = &mut self.enemies[n];
+			let died = moving_target.life < self.config.enemy_trap_death_life;
+			if died {
+				self.enemies.remove(n);
+				self.grid.remove_enemy(trap_idx);
+			} else {
+				moving_target.points = 0;
+				moving_target.life = self.config.enemy_trap_life;
 			}
+			self.emit(GameEvent::EnemyHitTrap { idx: trap_idx, died });
+			self.recreate_trap_cell();
 		}
 	}
 
 	fn check_moving_target_consume_reward(&mut self) {
-		let reward_idx = self.reward_cell_idx();
-		if let Some(moving_target) = &mut self.moving_cell {
-			if moving_target.idx == reward_idx {
-				moving_target.life += 25;
-				moving_target.points += 150;
-				self.reward_cell = WorldMap::generate_reward_cell(self.get_2d_size(), &self.snake.body);
-			}
+		let Some(reward_idx) = self.reward_cell_idx() else {
+			return;
+		};
+		if let Some(n) = self.hungriest_enemy_at(reward_idx) {
+			let moving_target = &mut self.enemies[n];
+			moving_target.life += self.config.enemy_reward_life;
+			moving_target.points += self.config.enemy_reward_points;
+			self.respawn_reward();
+			self.emit(GameEvent::EnemyAteReward { idx: reward_idx });
 		}
 	}
 
 	fn check_moving_target_consume_super_bonus(&mut self) {
-		let bonus_idx = self.super_bonus_cell_idx();
+		let Some(bonus_idx) = self.super_bonus_cell_idx() else {
+			return;
+		};
 		let bonus_poinst = self.super_bonus_points();
-		if let Some(moving_target) = &mut self.moving_cell {
-			if moving_target.idx == bonus_idx {
-				moving_target.life += 25;
-				moving_target.points += bonus_poinst + 300;
-				self.super_bonus_cell = Some(WorldMap::generate_super_bonus(self.get_2d_size(), &self.snake.body));
-			}
+		if let Some(n) = self.hungriest_enemy_at(bonus_idx) {
+			let moving_target = &mut self.enemies[n];
+			moving_target.life += self.config.enemy_bonus_life;
+			moving_target.points += bonus_poinst + self.config.enemy_bonus_points;
+			self.set_super_bonus(None);
+			self.emit(GameEvent::EnemyAteSuperBonus { idx: bonus_idx });
+			self.respawn_super_bonus();
 		}
 	}
 
+	// enemies go after the snake closest to them
+	fn nearest_player(&self, idx: usize) -> usize {
+		self.alive_players().into_iter()
+			.min_by_key(|p| behavior::cell_distance(idx, self.players[*p].head(), self.width, self.height, self.boundary_mode))
+			.unwrap_or(0)
+	}
+
 	fn check_moving_target(&mut self) {
-		if let Some(moving_target) = &mut self.moving_cell {
+		let had_room = self.enemies.len() < self.allowed_enemies();
+		let mut n = 0;
+		while n < self.enemies.len() {
+			let target = self.nearest_player(self.enemies[n].idx);
+			let moving_target = &mut self.enemies[n];
 			if moving_target.life == 0 {
-				drop(moving_target);
-				self.remove_moving_target();
-				return;
+				let idx = moving_target.idx;
+				self.remove_moving_target(n);
+				self.emit(GameEvent::EnemyExpired { idx });
+				continue;
 			}
 			if moving_target.decision_steps == 0 {
-				moving_target.change_direction();
-				// 
-				
-				let map_length = self.size;
-				moving_target.next_move(map_length);
+				let view = EnemyView {
+					position: moving_target.idx,
+					direction: moving_target.direction,
+					status: moving_target.status,
+					width: self.width,
+					height: self.height,
+					boundary_mode: self.boundary_mode,
+					blocked: &self.blocked_cells,
+					snake: &self.players[target].snake.body,
+					reward: self.reward_cell.as_ref().map(|reward| reward.idx),
+					super_bonus: self.super_bonus_cell.as_ref().map(|bonus| bonus.0),
+					trap: self.trap_cell.as_ref().map(|trap| trap.idx)
+				};
+				moving_target.change_direction(&view, self.rng.as_mut());
+				let from = moving_target.idx;
+				moving_target.next_move(self.width, self.height, self.boundary_mode, &self.blocked_cells, self.config.enemy_rest_steps, self.rng.as_mut());
+				self.grid.remove_enemy(from);
+				self.grid.add_enemy(moving_target.idx);
 			} else {
 				moving_target.decision_steps -= 1;
 			}
 			moving_target.decrease_life_steps();
-		} else {
-			if self.steps_to_moving_target == 0 {
-				if self.snake_length() < self.get_2d_size() - self.size {
-					self.moving_cell = Some(WorldMap::generate_moving_target(self.get_2d_size(), &self.snake.body));
+			n += 1;
+		}
+		self.check_enemy_collisions();
+
+		if !had_room {
+			return;
+		}
+		if self.steps_to_moving_target == 0 {
+			if self.snake_cells_total() < self.free_cells().saturating_sub(self.width) {
+				// no empty cell, the countdown stays at 0 and the next tick tries again
+				let Some(enemy) = WorldMap::generate_moving_target(&self.grid, &self.config, self.rng.as_mut()) else {
+					return;
+				};
+				self.grid.add_enemy(enemy.idx);
+				self.emit(GameEvent::EnemySpawned { idx: enemy.idx });
+				self.enemies.push(enemy);
+				if self.enemies.len() < self.allowed_enemies() {
+					self.steps_to_moving_target = WorldMap::gen_moving_target_steps(self.side(), &self.config, self.rng.as_mut());
 				}
-			} else {
-				self.steps_to_moving_target -= 1;
 			}
+		} else {
+			self.steps_to_moving_target -= 1;
+		}
+	}
+
+	// two enemies on one cell, the one with more life swallows the other (older wins a tie)
+	fn check_enemy_collisions(&mut self) {
+		let mut n = 0;
+		while n < self.enemies.len() {
+			let idx = self.enemies[n].idx;
+			let other = self.enemies.iter().skip(n + 1).position(|enemy| enemy.idx == idx).map(|m| m + n + 1);
+			let Some(m) = other else {
+				n += 1;
+				continue;
+			};
+			let (winner, loser) = if self.enemies[m].life > self.enemies[n].life { (m, n) } else { (n, m) };
+			let life = self.enemies[loser].life / 2;
+			let points = self.enemies[loser].points / 2;
+			self.enemies[winner].life += life;
+			self.enemies[winner].points += points;
+			self.emit(GameEvent::EnemiesCollided { idx });
+			self.remove_moving_target(loser);
 		}
 	}
 
@@ -245,16 +816,10 @@ impl WorldMap {
 		snake.iter().position(|cell| cell.0 == idx)
 	}
 
-	fn generate_super_bonus(max: usize, snake_body: &Vec<SnakeCell>) -> SuperBonus {
-		let mut cell_idx: usize;
-		loop { 
-			cell_idx = rnd(max);
-			if !snake_body.contains(&SnakeCell(cell_idx)) {
-				break;
-			}
-		}
-		let life = 5;
-		SuperBonus(cell_idx, life)
+	fn generate_super_bonus(grid: &grid::Occupancy, config: &GameConfig, rng: &mut dyn GameRng) -> Option<SuperBonus> {
+		let cell_idx = grid.spawn_cell(rng)?;
+		let life = config.super_bonus_life;
+		Some(SuperBonus(cell_idx, life))
 	}
 
 	pub fn super_bonus_cell_life(&self) -> usize {
@@ -264,20 +829,44 @@ impl WorldMap {
 		}
 	}
 
-	pub fn super_bonus_consumption(&mut self) {
-		self.consumed_super_bonuses += 1;
-		self.bonus_points += self.super_bonus_points();
-		self.points += self.super_bonus_points();
-		self.super_bonus_cell = None;
+	fn super_bonus_consumption(&mut self, p: usize) {
+		let points = self.super_bonus_points();
+		let player = &mut self.players[p];
+		player.consumed_super_bonuses += 1;
+		player.bonus_points += points;
+		player.points += points;
+		self.emit(GameEvent::SuperBonusEaten { player: p, idx: self.players[p].head(), points });
+		self.set_super_bonus(None);
+	}
+
+	// no bonus this time when no cell is empty
+	fn respawn_super_bonus(&mut self) {
+		let bonus = WorldMap::generate_super_bonus(&self.grid, &self.config, self.rng.as_mut());
+		if let Some(bonus) = &bonus {
+			self.emit(GameEvent::SuperBonusSpawned { idx: bonus.0 });
+		}
+		self.set_super_bonus(bonus);
+	}
+
+	fn set_super_bonus(&mut self, bonus: Option<SuperBonus>) {
+		if let Some(old) = &self.super_bonus_cell {
+			self.grid.unset(old.0, grid::SUPER_BONUS);
+		}
+		if let Some(new) = &bonus {
+			self.grid.set(new.0, grid::SUPER_BONUS);
+		}
+		self.super_bonus_cell = bonus;
 	}
 
 	pub fn super_bonus_points(&self) -> usize{
-		300 + self.snake_length() * 10 
+		self.config.super_bonus_points.saturating_add(self.longest_snake().saturating_mul(self.config.super_bonus_points_per_cell))
 	}
 
-	fn check_super_bonus(&mut self) {
-		if self.snake_head_index() == self.super_bonus_cell_idx() {
-			self.super_bonus_consumption();
+	fn check_super_bonus(&mut self, movers: &[usize]) {
+		for &p in movers {
+			if Some(self.players[p].head()) == self.super_bonus_cell_idx() {
+				self.super_bonus_consumption(p);
+			}
 		}
 
 		if let Some(super_bonus_cell) = &mut self.super_bonus_cell {
@@ -288,7 +877,9 @@ impl WorldMap {
 			}
 
 			if super_bonus_cell.1 == 0 {
-				self.super_bonus_cell = None;
+				let idx = super_bonus_cell.0;
+				self.set_super_bonus(None);
+				self.emit(GameEvent::SuperBonusExpired { idx });
 			}
 		} 
 		self.super_bonus_steps -= 1;
@@ -296,41 +887,45 @@ impl WorldMap {
 		if self.super_bonus_steps != 0 {
 			return;
 		}
-		if self.snake_length() < self.get_2d_size() - self.size {
-			self.super_bonus_cell = Some(WorldMap::generate_super_bonus(self.get_2d_size(), &self.snake.body));
-			self.super_bonus_steps = WorldMap::gen_super_bonus_steps(self.size);
-		} 
+		// a crowded board skips this bonus, the countdown starts over either way
+		if self.snake_cells_total() < self.free_cells().saturating_sub(self.width) {
+			self.respawn_super_bonus();
+		}
+		self.super_bonus_steps = WorldMap::gen_super_bonus_steps(self.side(), &self.config, self.rng.as_mut());
 	}
 	
 
-	fn gen_super_bonus_steps(size: usize) -> usize {
-		rnd(size * 2) + 4
+	fn gen_super_bonus_steps(size: usize, config: &GameConfig, rng: &mut dyn GameRng) -> usize {
+		rng.rnd(size.saturating_mul(config.super_bonus_spawn_range)).saturating_add(config.super_bonus_spawn_steps)
 	}
 
-	pub fn super_bonus_cell_idx(&self) -> usize  {
-		match &self.super_bonus_cell {
-			None => 1000000,
-			Some(super_bonus) => super_bonus.0
-		}
+	// undefined in JS while there is no bonus
+	pub fn super_bonus_cell_idx(&self) -> Option<usize> {
+		self.super_bonus_cell.as_ref().map(|super_bonus| super_bonus.0)
 	}
 
 	pub fn super_bonus_steps(&self) -> usize {
 		self.super_bonus_steps
 	}
 
-	fn generate_reward_cell(max: usize, snake_body: &Vec<SnakeCell>) -> RewardCell {
-		// guard, insure the reward dont generate in snake body
-		let mut reward_cell_idx: usize;
-		loop { 
-			reward_cell_idx = rnd(max);
-			if !snake_body.contains(&SnakeCell(reward_cell_idx)) {
-				break;
-			}
+	// snake_len is the longest snake, the reward grows with it
+	// only on empty cells, None when there is none
+	fn generate_reward_cell(grid: &grid::Occupancy, snake_len: usize, rng: &mut dyn GameRng) -> Option<RewardCell> {
+		let reward_cell_idx = grid.spawn_cell(rng)?;
+		let reward_type: RewardType = WorldMap::define_reward_type(snake_len);
+		let points: usize = WorldMap::define_reward_points(snake_len, reward_type);
+		Some(RewardCell::new(reward_cell_idx, reward_type, points))
+	}
+
+	// the old reward goes away first, the new one may land anywhere else
+	fn respawn_reward(&mut self) {
+		if let Some(old) = self.reward_cell.take() {
+			self.grid.unset(old.idx, grid::REWARD);
+		}
+		self.reward_cell = WorldMap::generate_reward_cell(&self.grid, self.longest_snake(), self.rng.as_mut());
+		if let Some(reward) = &self.reward_cell {
+			self.grid.set(reward.idx, grid::REWARD);
 		}
-		let reward_type: RewardType = WorldMap::define_reward_type(snake_body.len());
-		let points: usize = WorldMap::define_reward_points(snake_body, reward_type);
-		let reward_cell: RewardCell = RewardCell::new(reward_cell_idx, reward_type, points);
-		reward_cell
 	}
 
 	fn define_reward_type(num: usize) -> RewardType {
@@ -342,8 +937,8 @@ impl WorldMap {
 		}
 	}
 
-	fn define_reward_points(snake_body: &Vec<SnakeCell>, reward_type: RewardType) -> usize {
-		let points: usize = snake_body.len();
+	fn define_reward_points(snake_len: usize, reward_type: RewardType) -> usize {
+		let points: usize = snake_len;
 		match reward_type {
 			RewardType::Yellow => points,
 			RewardType::Red => points + points * 2,
@@ -352,31 +947,37 @@ impl WorldMap {
 		}
 	}
 
-	fn consume_reward(&mut self) {
-		self.consumed_rewards += 1;
-		let bonus = self.comming_bonus_by_steps();
+	fn consume_reward(&mut self, p: usize) {
+		let Some(reward) = self.reward_cell else {
+			return;
+		};
+		let points_before = self.players[p].points;
+		let idx = reward.idx;
+		self.players[p].consumed_rewards += 1;
+		let bonus = self.players[p].comming_bonus(self._steps);
 		if bonus != 0 {
-			self.increase_points(bonus + 1);
+			self.increase_points(p, bonus + 1);
 		}
-		self.steps += self._steps;
-		self.add_points();
-		if self.snake_length() < self.get_2d_size() {
-			self.reward_cell = WorldMap::generate_reward_cell(self.get_2d_size(), &self.snake.body);
-		} else {  // win condition
-			self.status = Some(GameStatus::Won)
+		self.players[p].steps += self._steps;
+		self.players[p].points += reward.points;
+		self.emit(GameEvent::RewardEaten { player: p, idx, points: self.players[p].points - points_before });
+		self.respawn_reward();
+		if self.reward_cell.is_none() {  // win condition, the board is full
+			self.finish_on_points();
 		}
-		self.snake.body.push(SnakeCell(self.snake.body[1].0));
+		let player = &mut self.players[p];
+		let neck = player.snake.body[1];
+		player.snake.body.push_back(neck);
+		self.grid.add_snake(neck.0);
 	}
 
 	pub fn trap_steps(&self) -> usize {
 		self.trap_steps
 	}
 
-	pub fn trap_cell_idx(&self) -> usize  {
-		match &self.trap_cell {
-			None => 1000000,
-			Some(trap_cell) => trap_cell.idx
-		}
+	// undefined in JS while there is no trap
+	pub fn trap_cell_idx(&self) -> Option<usize> {
+		self.trap_cell.as_ref().map(|trap_cell| trap_cell.idx)
 	}
 
 	pub fn trap_color(&self) -> String {
@@ -393,16 +994,20 @@ impl WorldMap {
 		}
 	}
 
-	fn check_trap(&mut self) {
+	fn check_trap(&mut self, movers: &[usize]) {
 		if let Some(trap_cell) = &mut self.trap_cell {
 			trap_cell.life -= 1;
 			
 			if trap_cell.life == 0 {
-				self.clear_trap_cell()
+				let idx = trap_cell.idx;
+				self.clear_trap_cell();
+				self.emit(GameEvent::TrapExpired { idx });
 			}
 		}
-		if self.snake_head_index() == self.trap_cell_idx() {
-			self.consuming_trap();
+		for &p in movers {
+			if Some(self.players[p].head()) == self.trap_cell_idx() {
+				self.consuming_trap(p);
+			}
 		}
 
 		if self.trap_steps == 0 {
@@ -412,109 +1017,115 @@ impl WorldMap {
 		} 
 	}
 	
-	fn consuming_trap(&mut self) {
-		self.consumed_traps += 1;
-		if self.consumed_traps % 5 == 0 {
-			self.points += self.bonus_points;
+	fn consuming_trap(&mut self, p: usize) {
+		let player = &mut self.players[p];
+		player.consumed_traps += 1;
+		let bonus = player.consumed_traps.is_multiple_of(self.config.trap_bonus_every);
+		if bonus {
+			player.points += player.bonus_points;
 		} else {
-			self.points /= 2;
+			player.points /= self.config.trap_points_divisor;
 		}
-		self.snake.body.pop();
+		if player.snake.body.len() > 2 { // head and neck always stay
+			if let Some(tail) = player.snake.body.pop_back() {
+				self.grid.remove_snake(tail.0);
+			}
+		}
+		self.emit(GameEvent::TrapTriggered { player: p, idx: self.players[p].head(), bonus });
 		self.clear_trap_cell();
 	}
 
 	fn clear_trap_cell(&mut self) {
+		if let Some(trap) = &self.trap_cell {
+			self.grid.unset(trap.idx, grid::TRAP);
+		}
 		self.trap_cell = None;
 	}
 	
-	fn generate_trap_cell(max: usize, snake_body: &Vec<SnakeCell>) -> TrapCell {
-		let mut trap_cell_idx: usize;
-		loop {
-			trap_cell_idx = rnd(max);
-			if !snake_body.contains(&SnakeCell(trap_cell_idx)) {
-				break;
-			}
-		}
-		let rnd_num = rnd(4);
-		let life: usize = rnd(10) + 2;
-		let color = match rnd_num {
-			0 => String::from("#FFEAAE"),
-			1 => String::from("chocolate"),
-			2 => String::from("blueviolet"),
-			_ => String::from("brown"),
-		};
-		TrapCell::new(trap_cell_idx, life, color)
+	fn generate_trap_cell(grid: &grid::Occupancy, config: &GameConfig, rng: &mut dyn GameRng) -> Option<TrapCell> {
+		let trap_cell_idx = grid.spawn_cell(rng)?;
+		let rnd_num = rng.rnd(4);
+		let life: usize = rng.rnd(config.trap_life_range) + config.trap_life_min;
+		let color = String::from(ITEM_COLORS[rnd_num]);
+		Some(TrapCell::new(trap_cell_idx, life, color))
 	}
 
 	pub fn recreate_trap_cell(&mut self) {
-		if self.snake_length() < 3 || self.snake_length() > self.get_2d_size() - 10 { return; }
-		self.trap_cell = None;
-		self.trap_cell = Some(WorldMap::generate_trap_cell(self.get_2d_size(), &self.snake.body));
+		if self.longest_snake() < 3 || self.snake_cells_total() > self.free_cells().saturating_sub(10) { return; }
+		self.clear_trap_cell();
+		// no empty cell, trap_steps stays at 0 and the next tick tries again
+		self.trap_cell = WorldMap::generate_trap_cell(&self.grid, &self.config, self.rng.as_mut());
 		if let Some(trap_cell) = &self.trap_cell {
-			let new_steps = trap_cell.life + now() % self.size;
-			self.trap_steps += new_steps;
+			self.grid.set(trap_cell.idx, grid::TRAP);
+			let new_steps = trap_cell.life.saturating_add(self.rng.rnd(self.side().saturating_mul(self.config.trap_spawn_range)));
+			self.trap_steps = self.trap_steps.saturating_add(new_steps);
+			self.emit(GameEvent::TrapSpawned { idx: trap_cell.idx });
 		}
 	}
 
-	fn reduce_points(&mut self) {
-		self.reward_cell.points -= self.reward_cell.points / 3;
-		self.steps += self._steps;
+	fn reduce_points(&mut self, p: usize) {
+		if let Some(reward) = &mut self.reward_cell {
+			reward.points -= reward.points / 3;
+		}
+		self.players[p].steps += self._steps;
 	}
 
-	fn increase_points(&mut self, bonus: usize) {
-		self.bonus_points += bonus;
-		self.points += bonus;
+	fn increase_points(&mut self, p: usize, bonus: usize) {
+		self.players[p].bonus_points += bonus;
+		self.players[p].points += bonus;
 	}
 
 	pub fn points(&self) -> usize {
-		self.points
+		self.players[0].points
 	}
 
 	pub fn bonus_stat(&self) -> usize {
-		self.bonus_points
+		self.players[0].bonus_points
 	}
 
 	pub fn comming_bonus_by_steps(&self) -> usize {
-		if self.steps > self._steps {
-			self.steps
-		} else {
-			0
-		}
+		self.players[0].comming_bonus(self._steps)
 	}
 
 	pub fn get_reward_color(&self) -> String {
-		match self.reward_cell.reward_type {
-			Some(RewardType::Yellow) => String::from("#FFEAAE"),
-			Some(RewardType::Red) => String::from("chocolate"),
-			Some(RewardType::Blue) => String::from("blueviolet"),
-			Some(RewardType::Black) => String::from("brown"),
-			_ => String::from("cadetblue")
+		match self.reward_cell.and_then(|reward| reward.reward_type) {
+			Some(reward_type) => String::from(ITEM_COLORS[reward_type as usize]),
+			None => String::from("cadetblue")
 		}
 	} 
 
 	pub fn get_game_stat(&self) -> GameStat {
+		self.stat_of(&self.players[0])
+	}
+
+	// the player_* getters are undefined in JS for a player that doesn't exist
+	pub fn player_stat(&self, p: usize) -> Option<GameStat> {
+		self.players.get(p).map(|player| self.stat_of(player))
+	}
+
+	fn stat_of(&self, player: &Player) -> GameStat {
 		GameStat {
-			consumed_rewards: self.consumed_rewards,
-			consumed_traps: self.consumed_traps,
+			consumed_rewards: player.consumed_rewards,
+			consumed_traps: player.consumed_traps,
 			life_steps: self.life_steps,
-			bonus: self.bonus_points,
-			snake_size: self.max_snake_size,
-			super_bonuses: self.consumed_super_bonuses,
-			consumed_moving_targets: self.consumed_moving_targets,
-			points: self.points,
-			eaten_by_enemy: self.eaten_by_enemy
+			bonus: player.bonus_points,
+			snake_size: player.max_snake_size,
+			super_bonuses: player.consumed_super_bonuses,
+			consumed_moving_targets: player.consumed_moving_targets,
+			points: player.points,
+			eaten_by_enemy: player.eaten_by_enemy,
+			difficulty: self.config.difficulty
 		}
 	}
 
 	pub fn get_reward_points(&self) -> usize {
-		self.reward_cell.points
-	}
-
-	fn add_points(&mut self) {
-		self.points += self.reward_cell.points;
+		self.reward_cell.map_or(0, |reward| reward.points)
 	}
 
 	pub fn start_game(&mut self) {
+		if self.status.is_none() && self.board_edited {
+			self.restart_from_replay();
+		}
 		self.status = Some(GameStatus::Played);
 	}
 
@@ -523,6 +1134,13 @@ impl WorldMap {
 	}
 
 	pub fn game_status_text(&self) -> String {
+		if self.players.len() > 1 {
+			match (self.status, self.winner) {
+				(Some(GameStatus::Won), Some(p)) => return format!("Player {} has won!", p + 1),
+				(Some(GameStatus::Lost), _) => return String::from("Draw!"),
+				_ => {}
+			}
+		}
 		match self.status {
 			Some(GameStatus::Won) => String::from("You have won!"),
 			Some(GameStatus::Lost) => String::from("You have lost!"),
@@ -531,39 +1149,185 @@ impl WorldMap {
 		}
 	}
 
-	pub fn reward_cell_idx(&self) -> usize {
-		self.reward_cell.idx
+	// undefined in JS when the board has no room left for it
+	pub fn reward_cell_idx(&self) -> Option<usize> {
+		self.reward_cell.map(|reward| reward.idx)
 	}
 
+	// width of the board, same as height for square boards
 	pub fn size(&self) -> usize {
-		self.size
+		self.width
+	}
+
+	pub fn width(&self) -> usize {
+		self.width
+	}
+
+	pub fn height(&self) -> usize {
+		self.height
 	}
 
 	pub fn get_2d_size(&self) -> usize {
-		self.size * self.size
+		self.width * self.height
+	}
+
+	// Square board of new_size, only before the game starts.
+	// False (and nothing changes) when a snake, item, enemy or obstacle would be left outside.
+	pub fn set_size(&mut self, new_size: usize) -> bool {
+		let Some(cells) = new_size.checked_mul(new_size).filter(|cells| *cells <= MAX_CELLS && self.status.is_none()) else {
+			return false;
+		};
+		let used = self.players.iter().flat_map(|player| &player.snake.body).map(|cell| cell.0)
+			.chain(self.enemies.iter().map(|enemy| enemy.idx))
+			.chain(self.obstacles.iter().copied())
+			.chain(self.portals.iter().flat_map(|(entry, exit)| [*entry, *exit]))
+			.chain(self.reward_cell_idx())
+			.chain(self.trap_cell_idx())
+			.chain(self.super_bonus_cell_idx());
+		if used.max().is_some_and(|idx| idx >= cells) {
+			return false;
+		}
+		self.width = new_size;
+		self.height = new_size;
+		self.board_edited = true;
+		if let Some(replay) = &mut self.replay {
+			replay.level.width = new_size;
+			replay.level.height = new_size;
+		}
+		self.rebuild_grid();
+		true
 	}
 
-	pub fn set_size(&mut self, new_size: usize) {
-		self.size = new_size;
+	// average side length, scales the spawn timers on rectangular boards
+	fn side(&self) -> usize {
+		(self.width + self.height) / 2
 	}
 
 	pub fn snake_head_index(&self) -> usize {
-		self.snake.body[0].0 // body vector.
+		self.players[0].head()
 	}
 
 	pub fn get_steps(&self) -> usize {
-		self.steps
+		self.players[0].steps
+	}
+
+	// time between two updates for the current snake length
+	pub fn tick_interval_ms(&self) -> usize {
+		let length = self.longest_snake();
+		let speed = SPEED_CURVE.iter()
+			.find(|(max_length, _)| length <= *max_length)
+			.map_or(LONG_SNAKE_SPEED, |(_, speed)| *speed);
+		// at least 1ms, with 0 advance() would never run out of due ticks
+		(self.config.speed_ms.saturating_mul(10) / speed).max(1)
+	}
+
+	// fixed timestep, runs every update that is due after elapsed_ms and returns how many ran
+	pub fn advance(&mut self, elapsed_ms: usize) -> usize {
+		self.add_elapsed(elapsed_ms);
+		let mut ticks = 0;
+		while self.take_due_tick() {
+			self.update();
+			ticks += 1;
+		}
+		ticks
+	}
+
+	pub(crate) fn add_elapsed(&mut self, elapsed_ms: usize) {
+		let max = self.tick_interval_ms() * MAX_CATCH_UP_TICKS;
+		self.elapsed_ms = self.elapsed_ms.saturating_add(elapsed_ms).min(max);
+	}
+
+	// time only runs while the game is played
+	pub(crate) fn take_due_tick(&mut self) -> bool {
+		if self.status != Some(GameStatus::Played) {
+			self.elapsed_ms = 0;
+			return false;
+		}
+		let interval = self.tick_interval_ms();
+		if self.elapsed_ms < interval {
+			return false;
+		}
+		self.elapsed_ms -= interval;
+		true
 	}
 
 	pub fn change_snake_direction(&mut self, direction: Direction) {
-		let next_cell = self.generate_next_snake_cell(&direction);
+		self.change_player_direction(0, direction);
+	}
 
-		if self.snake.body[1].0 == next_cell.0 {
+	// unknown players, computer snakes and snakes on autopilot are ignored
+	pub fn change_player_direction(&mut self, p: usize, direction: Direction) {
+		if self.players.get(p).is_some_and(|player| player.controller.is_none() && !player.autopilot) {
+			self.queue_turn(p, direction);
+		}
+	}
+
+	// autopilot turns go through here as well, a replay can't tell them from key presses
+	fn queue_turn(&mut self, p: usize, direction: Direction) {
+		if let Some(replay) = &mut self.replay {
+			replay.record(self.life_steps, p, direction);
+		}
+		let player = &mut self.players[p];
+		// the last queued turn is the direction the snake will have when this one is due
+		let last = player.turns.back().copied().unwrap_or(player.snake.direction);
+		if direction == last || player.turns.len() >= MAX_QUEUED_TURNS {
 			return;
 		}
-		self.next_cell = Option::Some(next_cell);
+		player.turns.push_back(direction);
+	}
+
+	// Safe way to the reward around the trap and the enemies that keeps the tail reachable,
+	// the roomiest move when there is none. None when every move is deadly.
+	pub fn suggest_direction(&self) -> Option<Direction> {
+		self.suggest_player_direction(0)
+	}
+
+	// None for an unknown player too
+	pub fn suggest_player_direction(&self, p: usize) -> Option<Direction> {
+		self.players.get(p)?;
+		let mut view = self.snake_view(p);
+		for idx in self.enemy_cells() {
+			view.free[idx] = false;
+		}
+		view.careful_step(|idx| Some(idx) == view.reward)
+	}
+
+	// while it is on update() steers with suggest_direction and the keys do nothing
+	pub fn set_autopilot(&mut self, on: bool) {
+		self.set_player_autopilot(0, on);
+	}
+
+	pub fn set_player_autopilot(&mut self, p: usize, on: bool) {
+		if let Some(player) = self.players.get_mut(p) {
+			player.autopilot = on;
+		}
+	}
 
-		self.snake.direction = direction
+	pub fn autopilot(&self) -> bool {
+		self.players[0].autopilot
+	}
+
+	fn steer_autopilots(&mut self) {
+		for p in self.alive_players() {
+			if !self.players[p].autopilot || self.players[p].controller.is_some() {
+				continue;
+			}
+			if let Some(direction) = self.suggest_player_direction(p) {
+				self.queue_turn(p, direction);
+			}
+		}
+	}
+
+	// one turn per tick, reversals are checked against the body as it is now
+	fn apply_next_turn(&mut self, p: usize) {
+		while let Some(direction) = self.players[p].turns.pop_front() {
+			let snake = &self.players[p].snake;
+			if direction == snake.direction || self.generate_next_snake_cell(p, &direction) == Some(snake.body[1]) {
+				continue;
+			}
+			self.players[p].snake.direction = direction;
+			return;
+		}
 	}
 
 	// can't return a reference to JS its not allowed
@@ -571,104 +1335,364 @@ impl WorldMap {
 	// borrowing rules not apply to it -> solution
 	// (not considered to be save that much as references, but its necessary if working with interoperations between different langs)
 	pub fn snake_cells(&self) -> *const SnakeCell { // working with pointers
-		self.snake.body.as_ptr()
+		self.player_cells(0)
 	}
 
 	pub fn snake_length(&self) -> usize {
-		self.snake.body.len()
+		self.player_length(0)
 	}
 
-	fn generate_next_snake_cell(&self, direction: &Direction) -> SnakeCell {
-		let snake_idx = self.snake_head_index();
-		let row = snake_idx / self.size;
+	pub fn player_count(&self) -> usize {
+		self.players.len()
+	}
 
-		return match direction {
-			Direction::Right => { 
-				SnakeCell((row * self.size) + (snake_idx + 1) % self.size)
-			},
-			Direction::Left => { 
-				SnakeCell((row * self.size) + (snake_idx - 1) % self.size)
-			},
-			Direction::Up => { 
-				SnakeCell((snake_idx - self.size) % self.get_2d_size())
-			},
-			Direction::Down => { 
-				SnakeCell((snake_idx + self.size) % self.get_2d_size())
-			},
-		};
+	// same as snake_cells for any player, read player_length(p) cells from head to tail.
+	// Zero-copy into the body ring, the pointer moves with every update so read it each frame.
+	// An unknown player has no cells, a null pointer and length 0.
+	pub fn player_cells(&self, p: usize) -> *const SnakeCell {
+		self.players.get(p).map_or(std::ptr::null(), |player| player.snake.body.as_ptr())
 	}
 
-	fn consumed_items(&self) -> usize {
-		self.consumed_traps + self.consumed_rewards + self.consumed_super_bonuses + self.consumed_moving_targets
+	pub fn player_length(&self, p: usize) -> usize {
+		self.players.get(p).map_or(0, |player| player.snake.body.len())
+	}
+
+	pub fn player_head_index(&self, p: usize) -> Option<usize> {
+		self.players.get(p).map(|player| player.head())
+	}
+
+	pub fn player_points(&self, p: usize) -> Option<usize> {
+		self.players.get(p).map(|player| player.points)
+	}
+
+	pub fn player_alive(&self, p: usize) -> Option<bool> {
+		self.players.get(p).map(|player| player.alive())
+	}
+
+	// None while the game runs and on a draw
+	pub fn winner(&self) -> Option<usize> {
+		self.winner
+	}
+
+	// Another snake, only before the game starts. The body trails behind idx like the first one,
+	// false when it would lie on a snake, an obstacle or a portal.
+	pub fn add_player(&mut self, idx: usize, direction: Direction) -> bool {
+		let obstacles = self.obstacles.clone();
+		self.spawn_player(idx, direction, &obstacles)
+	}
+
+	fn spawn_player(&mut self, idx: usize, direction: Direction, obstacles: &[usize]) -> bool {
+		if self.status.is_some() || idx >= self.get_2d_size() {
+			return false;
+		}
+		let snake = self.snake_behind(idx, direction, self.config.snake_start_size.min(self.get_2d_size()), obstacles);
+		let overlaps = snake.body.iter().enumerate()
+			.any(|(i, cell)| snake.body[..i].contains(cell) || self.is_snake_cell(cell.0) || self.is_blocked(cell.0));
+		if overlaps {
+			return false;
+		}
+		for cell in &snake.body {
+			self.grid.add_snake(cell.0);
+		}
+		self.players.push(Player::new(snake, &self.config));
+		self.board_edited = true;
+		if let Some(replay) = &mut self.replay {
+			replay.level.players.push((idx, direction));
+		}
+		self.move_reward_off_snakes();
+		true
+	}
+
+	// Hands snake p to a controller (None gives it back to the keyboard), only before the game starts.
+	// Player 1 works too, a bot against bots is a headless balance test.
+	pub fn set_controller(&mut self, p: usize, controller: Option<ControllerKind>) -> bool {
+		if self.status.is_some() || p >= self.players.len() {
+			return false;
+		}
+		self.players[p].controller = controller;
+		self.board_edited = true;
+		self.players[p].turns.clear();
+		if let Some(replay) = &mut self.replay {
+			replay.level.controllers.retain(|(player, _)| *player != p);
+			replay.level.controllers.extend(controller.map(|controller| (p, controller)));
+		}
+		true
+	}
+
+	// None for people at the keyboard (and for unknown players)
+	pub fn player_controller(&self, p: usize) -> Option<ControllerKind> {
+		self.players.get(p).and_then(|player| player.controller)
+	}
+
+	// a computer rival, see add_player
+	pub fn add_bot(&mut self, idx: usize, direction: Direction, controller: ControllerKind) -> bool {
+		self.add_player(idx, direction) && self.set_controller(self.players.len() - 1, Some(controller))
+	}
+
+	// None when the head would go through a wall
+	fn generate_next_snake_cell(&self, p: usize, direction: &Direction) -> Option<SnakeCell> {
+		step_cell(self.players[p].head(), *direction, self.width, self.height, self.boundary_mode)
+			.map(|idx| SnakeCell(self.portal_exit(idx)))
+	}
+
+	pub fn boundary_mode(&self) -> BoundaryMode {
+		self.boundary_mode
+	}
+
+	// the board setters below only work before the game starts, a replay couldn't play the change again
+	pub fn set_boundary_mode(&mut self, boundary_mode: BoundaryMode) -> bool {
+		if self.status.is_some() {
+			return false;
+		}
+		self.boundary_mode = boundary_mode;
+		if let Some(replay) = &mut self.replay {
+			replay.level.boundary_mode = boundary_mode;
+		}
+		self.board_edited = true;
+		true
+	}
+
+	// Obstacles under the snake or on a portal are skipped, items standing on an obstacle are moved or removed.
+	pub fn set_obstacles(&mut self, cells: Vec<usize>) -> bool {
+		if self.status.is_some() {
+			return false;
+		}
+		let max = self.get_2d_size();
+		let mut obstacles: Vec<usize> = cells.into_iter()
+			.filter(|idx| *idx < max && !self.is_snake_cell(*idx))
+			.filter(|idx| !self.portals.iter().any(|(entry, exit)| entry == idx || exit == idx))
+			.collect();
+		obstacles.sort_unstable();
+		obstacles.dedup();
+		self.obstacles = obstacles;
+		self.board_edited = true;
+		if let Some(replay) = &mut self.replay {
+			replay.level.obstacles = self.obstacles.clone();
+		}
+		self.refresh_blocked_cells();
+		true
+	}
+
+	pub fn add_obstacle(&mut self, idx: usize) -> bool {
+		let mut cells = self.obstacles.clone();
+		cells.push(idx);
+		self.set_obstacles(cells)
+	}
+
+	pub fn is_obstacle(&self, idx: usize) -> bool {
+		idx < self.get_2d_size() && self.grid.has(idx, grid::OBSTACLE)
+	}
+
+	// same zero-copy view as snake_cells, read it as Uint32Array of obstacle_count() cells
+	pub fn obstacle_cells(&self) -> *const usize {
+		self.obstacles.as_ptr()
+	}
+
+	pub fn obstacle_count(&self) -> usize {
+		self.obstacles.len()
+	}
+
+	// Every entity drawn in a frame in one call, no strings cross the boundary.
+	// Read the header as Uint32Array of RenderRecord::WORDS, its data[0] counts all the records, itself included.
+	// The pointer is good until the next render_state call.
+	pub fn render_state(&mut self) -> *const RenderRecord {
+		self.render_records().as_ptr()
+	}
+
+	// portal pairs flattened, [entry, exit, entry, exit, ...]
+	pub fn set_portals(&mut self, cells: Vec<usize>) -> bool {
+		let pairs = cells.chunks_exact(2).map(|pair| (pair[0], pair[1])).collect();
+		self.set_portal_pairs(pairs)
+	}
+
+	pub fn portal_cells(&self) -> Vec<usize> {
+		self.portals.iter().flat_map(|(entry, exit)| [*entry, *exit]).collect()
+	}
+
+	// cells the snake can ever occupy
+	fn free_cells(&self) -> usize {
+		self.get_2d_size() - self.blocked_cells.len()
 	}
 
 	fn check_activity(&mut self) {
-		if self.life_steps % 100 == 0 && self.consumed_items() < self.life_steps / 20 {
-			self.reason = Reason::NotActive;
-			self.status = Some(GameStatus::Lost);
+		let every = self.config.activity_check_steps;
+		let per_item = self.config.activity_steps_per_item;
+		if every == 0 || per_item == 0 || !self.life_steps.is_multiple_of(every) {
+			return;
+		}
+		for p in self.alive_players() {
+			if self.players[p].consumed_items() < self.life_steps / per_item {
+				self.kill(p, Reason::NotActive);
+			}
 		}
 	}
 
 	pub fn get_reason(&self) -> String {
-		match self.reason {
+		WorldMap::reason_text(self.players[0].reason)
+	}
+
+	pub fn player_reason(&self, p: usize) -> Option<String> {
+		self.players.get(p).map(|player| WorldMap::reason_text(player.reason))
+	}
+
+	fn reason_text(reason: Reason) -> String {
+		match reason {
 			Reason::Eaten => String::from("Eaten by enemy"),
 			Reason::NotActive => String::from("Not active, death from hungry"),
 			Reason::StillAlive => String::from("More than alive!"),
-			Reason::Suiside => String::from("Suiside due to depression")
+			Reason::Suiside => String::from("Suiside due to depression"),
+			Reason::HitWall => String::from("Crashed into the wall"),
+			Reason::HitObstacle => String::from("Crashed into an obstacle"),
+			Reason::HitSnake => String::from("Crashed into another snake")
+		}
+	}
+
+	// the reason stays with the last death, a later bite still turns it into Eaten
+	fn kill(&mut self, p: usize, reason: Reason) {
+		let was_alive = self.players[p].alive();
+		self.players[p].reason = reason;
+		if was_alive {
+			self.emit(GameEvent::SnakeDied { player: p, reason });
+		}
+		let alive = self.alive_players();
+		match (self.players.len(), alive.len()) {
+			(1, _) | (_, 0) => self.finish(None),
+			(_, 1) => self.finish(Some(alive[0])),
+			_ => {}
+		}
+	}
+
+	// full board or score limit, the most points among the living wins, a tie is a draw
+	fn finish_on_points(&mut self) {
+		let alive = self.alive_players();
+		let Some(best) = alive.iter().map(|p| self.players[*p].points).max() else {
+			return;
+		};
+		let leaders: Vec<usize> = alive.into_iter().filter(|p| self.players[*p].points == best).collect();
+		self.finish(if leaders.len() == 1 { Some(leaders[0]) } else { None });
+	}
+
+	fn finish(&mut self, winner: Option<usize>) {
+		self.winner = winner;
+		self.status = Some(if winner.is_some() { GameStatus::Won } else { GameStatus::Lost });
+	}
+
+	fn check_score_limit(&mut self) {
+		let limit = self.config.score_limit;
+		if limit != 0 && self.status == Some(GameStatus::Played) && self.players.iter().any(|player| player.alive() && player.points >= limit) {
+			self.finish_on_points();
 		}
 	}
 
 	pub fn update(&mut self) {
+		let was_played = self.status == Some(GameStatus::Played);
+		if was_played {
+			self.steer_autopilots();
+		}
+		self.tick();
+		if let Some(status) = self.status.filter(|status| was_played && *status != GameStatus::Played) {
+			self.emit(GameEvent::GameOver { status, reason: self.players[0].reason, winner: self.winner });
+		}
+	}
+
+	// moves the snake, eats the enemy it runs into, false when it hit a wall or an obstacle
+	fn move_snake(&mut self, p: usize) -> bool {
+		self.apply_next_turn(p);
+		let next_cell = self.generate_next_snake_cell(p, &self.players[p].snake.direction);
+		let head = match next_cell {
+			Some(cell) if self.is_obstacle(cell.0) => {
+				self.kill(p, Reason::HitObstacle);
+				return false;
+			},
+			Some(cell) => cell,
+			None => {
+				self.kill(p, Reason::HitWall);
+				return false;
+			}
+		};
+		let eaten = self.enemy_at(head.0);
+		let body = &mut self.players[p].snake.body;
+		// the tail stays where it is when the snake eats an enemy
+		if eaten.is_none() {
+			if let Some(tail) = body.pop_back() {
+				self.grid.remove_snake(tail.0);
+			}
+		}
+		body.push_front(head);
+		self.grid.add_snake(head.0);
+		if let Some(n) = eaten {
+			self.consume_moving_target(p, n);
+		}
+		true
+	}
+
+	// heads meeting kill both snakes, a head in any body (dead snakes too) kills its snake
+	fn check_snake_collisions(&mut self, movers: &[usize]) {
+		let mut crashes = vec!();
+		for &p in movers {
+			let head = self.players[p].snake.body[0];
+			// the head alone on its cell, nothing to tell apart
+			if self.grid.snakes(head.0) < 2 {
+				continue;
+			}
+			if self.players[p].snake.body[1..].contains(&head) {
+				crashes.push((p, Reason::Suiside));
+			} else {
+				crashes.push((p, Reason::HitSnake));
+			}
+		}
+		for (p, reason) in crashes {
+			self.kill(p, reason);
+		}
+	}
+
+	fn tick(&mut self) {
 		self.check_max_poinst();
 		self.check_activity();
 		match self.status {
 			Some(GameStatus::Played) => {
 				self.life_steps += 1;
-				self.steps -= 1;
-				let temp = self.snake.body.clone(); // need to use derive(Clone) for cloning
-				match self.next_cell {
-					Option::Some(cell) => {
-						self.snake.body[0] = cell; // head
-						self.next_cell = Option::None;
-					},
-					Option::None => {
-						self.snake.body[0] = self.generate_next_snake_cell(&self.snake.direction);
+				self.steer_bots();
+				// snakes that die later in the tick still finish it, like a single snake always did
+				let mut movers = vec!();
+				for p in self.alive_players() {
+					self.players[p].steps -= 1;
+					if self.move_snake(p) {
+						movers.push(p);
 					}
-				}	
-				if Some(self.snake_head_index()) == Some(self.moving_target_cell_idx()){
-					self.consume_moving_target();
 				}
-
-				let snake_len: usize = self.snake_length();
-				for i in 1..snake_len {
-					self.snake.body[i] = SnakeCell(temp[i-1].0);
+				if movers.is_empty() {
+					return;
 				}
 
-				self.check_super_bonus();
+				self.check_super_bonus(&movers);
 				self.check_moving_target();
 
-				if self.steps == 0 {
-					self.reduce_points();
-				}
-
-				if self.snake.body[1..snake_len].contains(&self.snake.body[0]) {
-					self.reason = Reason::Suiside;
-					self.status = Some(GameStatus::Lost);
+				for &p in &movers {
+					if self.players[p].steps == 0 {
+						self.reduce_points(p);
+					}
 				}
 
-				if self.snake.body[1..snake_len].contains(&SnakeCell(self.moving_target_cell_idx())) {
-					self.moving_cell_bite_snake();
-				}
+				self.check_snake_collisions(&movers);
+				self.check_enemies_bite_snake(&movers);
 
 				// consuming reward cell
-				if Some(self.reward_cell_idx()) == Some(self.snake_head_index()) {
-					self.consume_reward();
+				for &p in &movers {
+					if self.reward_cell_idx() == Some(self.players[p].head()) {
+						self.consume_reward(p);
+					}
 				}
-				self.check_trap();
+				self.check_trap(&movers);
 
 				self.check_moving_target_consume_trap();
 				self.check_moving_target_consume_reward();
 				self.check_moving_target_consume_super_bonus();
+				// an enemy ate the reward when no cell was empty
+				if self.reward_cell.is_none() && self.status == Some(GameStatus::Played) {
+					self.respawn_reward();
+				}
+				self.check_score_limit();
 			},
 			None => {
 
@@ -680,29 +1704,124 @@ impl WorldMap {
 	}
 }
 
+// obstacles and portal cells, sorted
+pub(crate) fn blocked_cells(obstacles: &[usize], portals: &[(usize, usize)]) -> Vec<usize> {
+	let mut blocked = obstacles.to_vec();
+	for (entry, exit) in portals {
+		blocked.push(*entry);
+		blocked.push(*exit);
+	}
+	blocked.sort_unstable();
+	blocked.dedup();
+	blocked
+}
+
+// neighbour of idx on a width x height board, None when a wall is in the way
+pub(crate) fn step_cell(idx: usize, direction: Direction, width: usize, height: usize, boundary_mode: BoundaryMode) -> Option<usize> {
+	let row = idx / width;
+	let col = idx % width;
+	if boundary_mode == BoundaryMode::Walls {
+		let blocked = match direction {
+			Direction::Right => col + 1 == width,
+			Direction::Left => col == 0,
+			Direction::Up => row == 0,
+			Direction::Down => row + 1 == height
+		};
+		if blocked {
+			return None;
+		}
+	}
+	Some(match direction {
+		Direction::Right => (row * width) + (col + 1) % width,
+		Direction::Left => (row * width) + (col + width - 1) % width,
+		Direction::Up => ((row + height - 1) % height) * width + col,
+		Direction::Down => ((row + 1) % height) * width + col
+	})
+}
+
 #[derive(Clone, Copy, PartialEq)]
 pub struct SnakeCell(usize);
 struct Snake {
-	body: Vec<SnakeCell>,
+	body: body::SnakeBody,
 	direction: Direction
 }
 
 impl Snake {
-	fn new(spawn_index: usize, size: usize) -> Snake {
+	fn new(spawn_index: usize, size: usize, max: usize) -> Snake {
 		let mut body:Vec<SnakeCell> = vec!();
 		
 		for i in 0..size {
-			body.push(SnakeCell(spawn_index - i));
+			body.push(SnakeCell((spawn_index + max - i) % max));
 		}
 
 		Snake { 
-			body,
+			body: body::SnakeBody::new(&body),
 			direction: Direction::Up
 		}
 	}
 }
 
-#[wasm_bindgen]
+// one snake with its own input queue and score, the board and its items are shared
+struct Player {
+	snake: Snake,
+	turns: VecDeque<Direction>,
+	points: usize,
+	steps: usize,
+	bonus_points: usize,
+	consumed_rewards: usize,
+	consumed_traps: usize,
+	consumed_super_bonuses: usize,
+	consumed_moving_targets: usize,
+	eaten_by_enemy: usize,
+	max_snake_size: usize,
+	reason: Reason,
+	// None for people at the keyboard
+	controller: Option<ControllerKind>,
+	autopilot: bool
+}
+
+impl Player {
+	fn new(snake: Snake, config: &GameConfig) -> Player {
+		Player {
+			max_snake_size: snake.body.len(),
+			snake,
+			turns: VecDeque::new(),
+			points: 0,
+			steps: config.start_steps,
+			bonus_points: 0,
+			consumed_rewards: 0,
+			consumed_traps: 0,
+			consumed_super_bonuses: 0,
+			consumed_moving_targets: 0,
+			eaten_by_enemy: 0,
+			reason: Reason::StillAlive,
+			controller: None,
+			autopilot: false
+		}
+	}
+
+	fn alive(&self) -> bool {
+		self.reason == Reason::StillAlive
+	}
+
+	fn head(&self) -> usize {
+		self.snake.body[0].0
+	}
+
+	fn consumed_items(&self) -> usize {
+		self.consumed_traps + self.consumed_rewards + self.consumed_super_bonuses + self.consumed_moving_targets
+	}
+
+	fn comming_bonus(&self, reward_steps: usize) -> usize {
+		if self.steps > reward_steps {
+			self.steps
+		} else {
+			0
+		}
+	}
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(Clone, Copy)]
 pub struct RewardCell {
 	idx: usize,
@@ -710,7 +1829,7 @@ pub struct RewardCell {
 	points: usize
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl RewardCell {
 	fn new(idx: usize, reward_type: RewardType, points: usize) -> RewardCell {
 		RewardCell { 
@@ -721,7 +1840,7 @@ impl RewardCell {
 	}
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(Clone)]
 pub struct TrapCell{
 	idx: usize,
@@ -729,7 +1848,7 @@ pub struct TrapCell{
 	color: String
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl TrapCell {
 	pub fn new(idx: usize, life: usize, color: String) -> TrapCell {
 		TrapCell { 
@@ -743,39 +1862,80 @@ impl TrapCell {
 	}
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub struct SuperBonus(usize, usize);
 
-#[wasm_bindgen]
-#[derive(PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub enum Direction {
 	Up, Right, Down, Left
 }
 
-#[wasm_bindgen]
+impl Direction {
+	pub fn opposite(&self) -> Direction {
+		match self {
+			Direction::Up => Direction::Down,
+			Direction::Right => Direction::Left,
+			Direction::Down => Direction::Up,
+			Direction::Left => Direction::Right
+		}
+	}
+
+	pub(crate) fn from_index(idx: usize) -> Option<Direction> {
+		match idx {
+			0 => Some(Direction::Up),
+			1 => Some(Direction::Right),
+			2 => Some(Direction::Down),
+			3 => Some(Direction::Left),
+			_ => None
+		}
+	}
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(Clone, Copy)]
 pub enum TargetStatus {
 	VeryHungry, Hungry, Fine, Good, VeryGood
 }
 
-#[wasm_bindgen]
-#[derive(Clone, Copy)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, PartialEq, Serialize)]
 pub enum  GameStatus {
 	Won, Lost, Played
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, PartialEq, Serialize)]
 pub enum Reason {
-	StillAlive, Eaten, NotActive, Suiside
+	StillAlive, Eaten, NotActive, Suiside, HitWall, HitObstacle, HitSnake
 }
 
-#[wasm_bindgen]
+// what happens at the board edges
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+pub enum BoundaryMode {
+	#[default]
+	Wrap,
+	Walls
+}
+
+impl BoundaryMode {
+	pub(crate) fn from_index(idx: usize) -> Option<BoundaryMode> {
+		match idx {
+			0 => Some(BoundaryMode::Wrap),
+			1 => Some(BoundaryMode::Walls),
+			_ => None
+		}
+	}
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(PartialEq, Clone, Copy)]
 pub enum RewardType {
 	Yellow, Red, Blue, Black
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub struct GameStat {
 	pub consumed_rewards: usize,
 	pub consumed_traps: usize,
@@ -785,10 +1945,12 @@ pub struct GameStat {
 	pub super_bonuses: usize,
 	pub consumed_moving_targets: usize,
 	pub points: usize,
-	pub eaten_by_enemy: usize
+	pub eaten_by_enemy: usize,
+	// points only compare within the same difficulty
+	pub difficulty: Difficulty
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub struct MovingTarget {
 	idx: usize,
 	direction: Direction,
@@ -799,26 +1961,26 @@ pub struct MovingTarget {
 	status: TargetStatus
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl MovingTarget {
-	pub fn new(idx: usize) -> MovingTarget {
-		let direction: Direction = MovingTarget::decide_direction();
-		let decision_steps: usize = rnd(3);
+	fn new(idx: usize, config: &GameConfig, rng: &mut dyn GameRng) -> MovingTarget {
+		let direction: Direction = MovingTarget::decide_direction(rng);
+		let decision_steps: usize = rng.rnd(3);
 		let status = TargetStatus::Good;
-		let steps_to_move: usize = MovingTarget::gen_move_steps(status);
+		let steps_to_move: usize = MovingTarget::gen_move_steps(status, config.enemy_rest_steps, rng);
 		MovingTarget { 
 			idx,
 			direction, 
-			points: 500,
-			life: 50,
+			points: config.enemy_points,
+			life: config.enemy_life,
 			decision_steps,
 			steps_to_move,
 			status
 		}
 	}
 
-	fn decide_direction() -> Direction {
-		let rnd_direction: usize = rnd(5);
+	fn decide_direction(rng: &mut dyn GameRng) -> Direction {
+		let rnd_direction: usize = rng.rnd(5);
 		match rnd_direction {
 			0 => Direction::Right,
 			1 => Direction::Left,
@@ -847,8 +2009,8 @@ impl MovingTarget {
 		}
 	}
 
-	pub fn calculate_points(&self) -> usize {
-		self.points + self.life * 15
+	pub fn calculate_points(&self, points_per_life: usize) -> usize {
+		self.points.saturating_add(self.life.saturating_mul(points_per_life))
 	}
 
 	pub fn position(&self) -> usize {
@@ -860,37 +2022,462 @@ impl MovingTarget {
 		self.check_status();
 	}
 
-	fn change_direction(&mut self) {
-		let rnd_range: usize = rnd(10);
-		self.direction = match rnd_range {
-			0..=5 => MovingTarget::decide_direction(),
-			_ => self.direction
+	fn change_direction(&mut self, view: &EnemyView, rng: &mut dyn GameRng) {
+		if let Some(direction) = behavior::behavior_for(view).next_direction(view, rng) {
+			self.direction = direction;
 		}
 	}
 
-	fn gen_move_steps(status:TargetStatus) -> usize{
+	fn gen_move_steps(status:TargetStatus, rest_steps: usize, rng: &mut dyn GameRng) -> usize{
 		match status {
-			TargetStatus::VeryGood => { rnd(2) + 2 },
-			TargetStatus::Good => { rnd(2) + 2 },
-			TargetStatus::Fine => { rnd(2) + 1 },
-			TargetStatus::Hungry => { rnd(2) + 1 },
-			TargetStatus::VeryHungry => { rnd(1) + 1 }
+			TargetStatus::VeryGood => { rng.rnd(2) + rest_steps + 1 },
+			TargetStatus::Good => { rng.rnd(2) + rest_steps + 1 },
+			TargetStatus::Fine => { rng.rnd(2) + rest_steps },
+			TargetStatus::Hungry => { rng.rnd(2) + rest_steps },
+			TargetStatus::VeryHungry => { rng.rnd(1) + rest_steps }
 		}
 	}
 
-	fn next_move(&mut self, map_length: usize) {
-		let row = self.idx / map_length;
+	fn next_move(&mut self, width: usize, height: usize, boundary_mode: BoundaryMode, blocked: &[usize], rest_steps: usize, rng: &mut dyn GameRng) {
 		if self.steps_to_move == 0 {
-			self.idx = match self.direction {
-				Direction::Right => { (row * map_length) + (self.idx + 1) % map_length },
-				Direction::Left => { (row * map_length) + (self.idx - 1) % map_length },
-				Direction::Up => { (self.idx - map_length) % (map_length * map_length) },
-				Direction::Down => { (self.idx + map_length) % (map_length * map_length) },
+			let free = |direction: Direction| {
+				step_cell(self.idx, direction, width, height, boundary_mode)
+					.filter(|idx| blocked.binary_search(idx).is_err())
+			};
+			self.idx = match free(self.direction) {
+				Some(idx) => idx,
+				None => { // steer around the wall or obstacle, turn aside first, bounce back last
+					let (side_a, side_b) = match self.direction {
+						Direction::Up | Direction::Down => (Direction::Left, Direction::Right),
+						Direction::Left | Direction::Right => (Direction::Up, Direction::Down)
+					};
+					let sides = if rng.rnd(2) == 0 { [side_a, side_b] } else { [side_b, side_a] };
+					let turn = [sides[0], sides[1], self.direction.opposite()].into_iter()
+						.find_map(|direction| free(direction).map(|idx| (direction, idx)));
+					match turn {
+						Some((direction, idx)) => {
+							self.direction = direction;
+							idx
+						},
+						None => self.idx
+					}
+				}
 			};
-			self.steps_to_move = MovingTarget::gen_move_steps(self.status);
+			self.steps_to_move = MovingTarget::gen_move_steps(self.status, rest_steps, rng);
 		} else {
 			self.steps_to_move -= 1;
 		}
 		
 	}
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn level_game(text: &str) -> WorldMap {
+		let mut world = WorldMap::from_level_str_with_seed(text, 5).ok().unwrap();
+		world.start_game();
+		world
+	}
+
+	#[test]
+	fn same_seed_same_game() {
+		let play = |seed: u64| {
+			let mut world = WorldMap::new_with_seed(10, 45, seed);
+			world.set_autopilot(true);
+			world.start_game();
+			for _ in 0..400 {
+				world.update();
+			}
+			world.to_snapshot()
+		};
+		assert_eq!(play(9), play(9));
+		assert_ne!(play(9), play(10));
+	}
+
+	#[test]
+	fn one_turn_per_tick() {
+		let text = "........\n........\n........\n...>....\n........\n........\n";
+		let mut world = level_game(text);
+		// left alone would be a reversal, after up it is a turn
+		world.change_snake_direction(Direction::Up);
+		world.change_snake_direction(Direction::Left);
+		world.update();
+		assert_eq!(world.snake_head_index(), 19);
+		world.update();
+		assert_eq!(world.snake_head_index(), 18);
+		world.update();
+		assert_eq!(world.snake_head_index(), 17);
+	}
+
+	#[test]
+	fn reversal_is_ignored() {
+		let text = "........\n........\n........\n...>....\n........\n........\n";
+		let mut world = level_game(text);
+		world.change_snake_direction(Direction::Left);
+		world.update();
+		assert_eq!(world.snake_head_index(), 28);
+		// the same direction twice doesn't take a second tick
+		world.change_snake_direction(Direction::Down);
+		world.change_snake_direction(Direction::Down);
+		world.change_snake_direction(Direction::Right);
+		world.update();
+		assert_eq!(world.snake_head_index(), 36);
+		world.update();
+		assert_eq!(world.snake_head_index(), 37);
+	}
+
+	#[test]
+	fn no_suggestion_for_an_unknown_player() {
+		let world = level_game("........\n..>.....\n........\n");
+		assert!(world.suggest_player_direction(0).is_some());
+		assert!(world.suggest_player_direction(5).is_none());
+	}
+
+	#[test]
+	fn suggestion_heads_for_the_reward() {
+		let mut world = level_game("........\n..>.....\n........\n");
+		world.reward_cell = Some(RewardCell::new(2, RewardType::Yellow, 2));
+		assert!(world.suggest_direction() == Some(Direction::Up));
+		// around an enemy in the way
+		world.reward_cell = Some(RewardCell::new(12, RewardType::Yellow, 2));
+		world.enemies = vec!(enemy(11, Direction::Up, 50));
+		assert!(world.suggest_direction() == Some(Direction::Up));
+
+		let world = level_game("boundary: walls\n.......>\n........\n");
+		assert!(world.suggest_direction() == Some(Direction::Down));
+	}
+
+	#[test]
+	fn autopilot_ignores_the_keys() {
+		let mut world = level_game("........\n..>.....\n........\n");
+		world.reward_cell = Some(RewardCell::new(2, RewardType::Yellow, 2));
+		world.set_autopilot(true);
+		world.change_snake_direction(Direction::Down);
+		world.update();
+		assert_eq!(world.snake_head_index(), 2);
+		assert_eq!(world.get_game_stat().consumed_rewards, 1);
+		world.set_autopilot(false);
+		world.change_snake_direction(Direction::Left);
+		world.update();
+		assert_eq!(world.snake_head_index(), 1);
+	}
+
+	#[test]
+	fn tiny_speed_still_paces() {
+		let config = GameConfig { speed_ms: 1, ..GameConfig::default() };
+		let mut world = WorldMap::new_with_config_and_seed(10, 45, config, 1);
+		world.start_game();
+		assert_eq!(world.tick_interval_ms(), 1);
+		assert_eq!(world.advance(16), MAX_CATCH_UP_TICKS);
+	}
+
+	#[test]
+	fn advance_runs_the_due_ticks() {
+		let mut world = level_game("........\n........\n..>.....\n........\n");
+		let interval = world.tick_interval_ms();
+		assert!(interval > 1);
+		assert_eq!(world.advance(interval - 1), 0);
+		assert_eq!(world.advance(1), 1);
+		assert_eq!(world.snake_head_index(), 19);
+		assert_eq!(world.advance(interval * 2 + interval / 2), 2);
+		// the half interval left over counts for the next tick
+		assert_eq!(world.advance(interval - interval / 2), 1);
+		// a long stall only catches up a few ticks
+		assert_eq!(world.advance(interval * 100), MAX_CATCH_UP_TICKS);
+		assert_eq!(world.advance(0), 0);
+	}
+
+	#[test]
+	fn time_only_runs_while_played() {
+		let mut world = WorldMap::from_level_str_with_seed("........\n..>.....\n", 5).ok().unwrap();
+		let interval = world.tick_interval_ms();
+		assert_eq!(world.advance(interval * 3), 0);
+		assert_eq!(world.snake_head_index(), 10);
+		// nothing saved up from before the start
+		world.start_game();
+		assert_eq!(world.advance(interval - 1), 0);
+	}
+
+	#[test]
+	fn spawn_body_keeps_off_walls_and_obstacles() {
+		let config = GameConfig { snake_start_size: 5, ..GameConfig::default() };
+		assert!(WorldMap::from_level_str_with_config("boundary: walls\n#..>....\n", config).is_err());
+
+		let mut level = Level::new(8, 1, 3);
+		level.snake_direction = Some(Direction::Right);
+		level.boundary_mode = BoundaryMode::Walls;
+		level.obstacles = vec![0];
+		let world = WorldMap::from_level_with_config(&level, config, Some(1));
+		assert_eq!(world.obstacle_count(), 1);
+		assert!(world.players[0].snake.body.iter().all(|cell| cell.0 != 0));
+	}
+
+	#[test]
+	fn bots_play_on_their_own() {
+		let mut world = WorldMap::new_with_seed(12, 50, 3);
+		assert!(!world.set_controller(1, Some(ControllerKind::Survival)));
+		assert!(world.add_bot(100, Direction::Left, ControllerKind::Greedy));
+		world.set_autopilot(true);
+		world.start_game();
+		assert!(!world.add_bot(30, Direction::Left, ControllerKind::Greedy));
+		assert!(!world.set_controller(1, None));
+		// steering a bot by hand does nothing
+		world.change_player_direction(1, Direction::Up);
+		assert!(world.players[1].turns.is_empty());
+		for _ in 0..300 {
+			world.update();
+		}
+		assert!(world.player_stat(1).unwrap().consumed_rewards > 0);
+	}
+
+	#[test]
+	fn missing_items_are_none() {
+		let mut world = level_game("........\n..>.....\n........\n");
+		world.reward_cell = None;
+		world.trap_cell = None;
+		world.set_super_bonus(None);
+		world.enemies.clear();
+		assert!(world.reward_cell_idx().is_none());
+		assert!(world.trap_cell_idx().is_none());
+		assert!(world.super_bonus_cell_idx().is_none());
+		assert!(world.moving_target_cell_idx().is_none());
+		assert!(world.player_head_index(1).is_none());
+		assert!(world.player_points(1).is_none());
+		assert!(world.player_controller(0).is_none());
+		assert!(world.winner().is_none());
+
+		world.reward_cell = Some(RewardCell::new(3, RewardType::Red, 9));
+		world.trap_cell = Some(TrapCell::new(4, 5, String::from(ITEM_COLORS[0])));
+		world.set_super_bonus(Some(SuperBonus(5, 3)));
+		world.enemies.push(enemy(6, Direction::Up, 40));
+		world.enemies.push(enemy(7, Direction::Up, 40));
+		assert_eq!(world.reward_cell_idx(), Some(3));
+		assert_eq!(world.trap_cell_idx(), Some(4));
+		assert_eq!(world.super_bonus_cell_idx(), Some(5));
+		// the oldest enemy
+		assert_eq!(world.moving_target_cell_idx(), Some(6));
+		assert_eq!(world.player_head_index(0), Some(10));
+		assert_eq!(world.player_alive(0), Some(true));
+	}
+
+	#[test]
+	fn heads_meeting_kill_both() {
+		let mut world = level_game("........\n..>.<...\n........\n");
+		world.update();
+		assert!(world.players.iter().all(|player| player.reason == Reason::HitSnake));
+		assert!(world.game_status() == Some(GameStatus::Lost));
+		assert_eq!(world.winner(), None);
+	}
+
+	#[test]
+	fn head_in_a_body_kills_its_snake() {
+		let mut world = level_game("........\n........\n..>.....\n.^......\n........\n........\n");
+		world.update();
+		assert_eq!(world.player_alive(0), Some(true));
+		assert!(world.players[1].reason == Reason::HitSnake);
+		assert!(world.game_status() == Some(GameStatus::Won));
+		assert_eq!(world.winner(), Some(0));
+	}
+
+	fn enemy(idx: usize, direction: Direction, life: usize) -> MovingTarget {
+		MovingTarget {
+			idx,
+			direction,
+			points: 500,
+			life,
+			decision_steps: 100,
+			steps_to_move: 0,
+			status: TargetStatus::Fine
+		}
+	}
+
+	#[test]
+	fn walls_end_the_game() {
+		let mut world = level_game("boundary: walls\n......>.\n........\n");
+		world.update();
+		assert!(world.game_status() == Some(GameStatus::Played));
+		world.update();
+		assert!(world.players[0].reason == Reason::HitWall);
+		assert!(world.game_status() == Some(GameStatus::Lost));
+
+		let mut world = level_game("boundary: wrap\n......>.\n........\n");
+		world.update();
+		world.update();
+		assert_eq!(world.snake_head_index(), 0);
+		assert!(world.game_status() == Some(GameStatus::Played));
+	}
+
+	#[test]
+	fn eating_and_dying_are_events() {
+		let mut world = level_game("boundary: walls\n.....>..\n........\n");
+		world.drain_events();
+		world.reward_cell = Some(RewardCell::new(6, RewardType::Yellow, 4));
+		world.update();
+		let events = world.drain_events();
+		assert!(events.iter().any(|event| matches!(event, GameEvent::RewardEaten { player: 0, idx: 6, points } if *points >= 4)));
+		assert!(world.drain_events().is_empty());
+
+		world.update();
+		world.update();
+		let events = world.drain_events();
+		assert!(events.contains(&GameEvent::SnakeDied { player: 0, reason: Reason::HitWall }));
+		assert!(events.last() == Some(&GameEvent::GameOver { status: GameStatus::Lost, reason: Reason::HitWall, winner: None }));
+	}
+
+	#[test]
+	fn events_json_and_cap() {
+		let mut world = level_game("........\n..>.....\n");
+		world.drain_events();
+		world.emit(GameEvent::TrapSpawned { idx: 3 });
+		assert_eq!(world.drain_events_json(), "[{\"type\":\"TrapSpawned\",\"idx\":3}]");
+		assert_eq!(world.drain_events_json(), "[]");
+		// undrained events only keep the newest
+		for idx in 0..MAX_EVENTS + 10 {
+			world.emit(GameEvent::TrapExpired { idx });
+		}
+		let events = world.drain_events();
+		assert_eq!(events.len(), MAX_EVENTS);
+		assert!(events[0] == GameEvent::TrapExpired { idx: 10 });
+	}
+
+	#[test]
+	fn enemy_turns_at_a_wall() {
+		let mut rng = SeededRng::new(1);
+		let mut walls = enemy(7, Direction::Right, 50);
+		walls.next_move(8, 4, BoundaryMode::Walls, &[], 0, &mut rng);
+		// up is a wall too, down is the only way aside
+		assert_eq!(walls.idx, 15);
+		assert!(walls.direction == Direction::Down);
+
+		let mut corner = enemy(0, Direction::Up, 50);
+		corner.next_move(1, 3, BoundaryMode::Walls, &[], 0, &mut rng);
+		assert_eq!(corner.idx, 1);
+		assert!(corner.direction == Direction::Down);
+
+		let mut wrap = enemy(7, Direction::Right, 50);
+		wrap.next_move(8, 4, BoundaryMode::Wrap, &[], 0, &mut rng);
+		assert_eq!(wrap.idx, 0);
+	}
+
+	#[test]
+	fn enemies_ramp_up_to_the_max() {
+		let config = GameConfig { max_enemies: 3, enemy_ramp_steps: 10, ..GameConfig::default() };
+		let mut world = WorldMap::new_with_config_and_seed(10, 45, config, 1);
+		assert_eq!(world.allowed_enemies(), 1);
+		world.life_steps = 10;
+		assert_eq!(world.allowed_enemies(), 2);
+		world.life_steps = 1000;
+		assert_eq!(world.allowed_enemies(), 3);
+		world.config.enemy_ramp_steps = 0;
+		world.life_steps = 0;
+		assert_eq!(world.allowed_enemies(), 3);
+
+		let config = GameConfig { max_enemies: 3, enemy_ramp_steps: 50, enemy_spawn_steps: 1, ..GameConfig::default() };
+		let mut world = WorldMap::new_with_config_and_seed(20, 210, config, 4);
+		world.set_autopilot(true);
+		world.start_game();
+		let mut most = 0;
+		for _ in 0..1000 {
+			world.update();
+			assert!(world.enemy_count() <= world.allowed_enemies());
+			most = most.max(world.enemy_count());
+		}
+		assert_eq!(most, 3);
+	}
+
+	#[test]
+	fn stronger_enemy_swallows_the_other() {
+		let mut world = level_game("........\n..>.....\n........\n........\n");
+		world.enemies.clear();
+		for enemy in [enemy(20, Direction::Right, 30), enemy(20, Direction::Left, 50), enemy(25, Direction::Up, 10)] {
+			world.grid.add_enemy(enemy.idx);
+			world.enemies.push(enemy);
+		}
+		world.drain_events();
+		world.check_enemy_collisions();
+		assert_eq!(world.enemy_count(), 2);
+		assert!(world.enemies[0].direction == Direction::Left);
+		assert_eq!((world.enemies[0].life, world.enemies[0].points), (65, 750));
+		assert!(world.drain_events() == vec!(GameEvent::EnemiesCollided { idx: 20 }));
+
+		// a tie goes to the older one
+		world.grid.remove_enemy(25);
+		world.grid.add_enemy(20);
+		world.enemies[1].idx = 20;
+		world.enemies[1].life = 65;
+		world.check_enemy_collisions();
+		assert_eq!(world.enemy_count(), 1);
+		assert_eq!(world.enemies[0].life, 97);
+	}
+
+	#[test]
+	fn rect_board_wraps_per_axis() {
+		let mut world = level_game("..........\n....^.....\n..........\n..........\n");
+		world.update();
+		assert_eq!(world.snake_head_index(), 4);
+		world.update();
+		assert_eq!(world.snake_head_index(), 34);
+
+		let mut world = level_game("..........\n........>.\n..........\n");
+		world.update();
+		assert_eq!(world.snake_head_index(), 19);
+		world.update();
+		assert_eq!(world.snake_head_index(), 10);
+	}
+
+	#[test]
+	fn rect_game_stays_on_the_board() {
+		let mut world = WorldMap::new_rect_with_seed(15, 4, 20, 3);
+		assert_eq!((world.width(), world.height(), world.get_2d_size()), (15, 4, 60));
+		world.set_autopilot(true);
+		world.start_game();
+		for _ in 0..600 {
+			world.update();
+			let cells = world.players[0].snake.body.iter().map(|cell| cell.0)
+				.chain(world.enemy_cells())
+				.chain(world.reward_cell_idx())
+				.chain(world.trap_cell_idx())
+				.chain(world.super_bonus_cell_idx());
+			assert!(cells.into_iter().all(|idx| idx < 60));
+		}
+	}
+
+	#[test]
+	fn obstacles_kill_and_block() {
+		let mut world = level_game("..>#....\n........\n");
+		assert!(world.is_obstacle(3));
+		world.update();
+		assert!(world.players[0].reason == Reason::HitObstacle);
+
+		// under the snake, off the board and twice
+		let mut world = WorldMap::new_with_seed(8, 20, 1);
+		assert!(world.set_obstacles(vec![20, 19, 30, 64, 30]));
+		assert_eq!(world.obstacle_count(), 1);
+
+		let mut rng = SeededRng::new(1);
+		let mut walker = enemy(9, Direction::Right, 50);
+		walker.next_move(8, 8, BoundaryMode::Wrap, &[10], 0, &mut rng);
+		assert!(walker.idx == 1 || walker.idx == 17);
+	}
+
+	#[test]
+	fn nothing_spawns_on_obstacles() {
+		let level = Level::bordered(8, 8, 27);
+		let mut world = WorldMap::from_level_with_config(&level, GameConfig::with_difficulty(Difficulty::Nightmare), Some(2));
+		world.set_autopilot(true);
+		world.start_game();
+		let mut spawned = 0;
+		for _ in 0..800 {
+			world.update();
+			spawned += world.drain_events().iter().filter(|event| matches!(event, GameEvent::TrapSpawned { .. } | GameEvent::EnemySpawned { .. })).count();
+			let items = world.enemy_cells().into_iter()
+				.chain(world.reward_cell_idx())
+				.chain(world.trap_cell_idx())
+				.chain(world.super_bonus_cell_idx());
+			assert!(items.into_iter().all(|idx| !level.obstacles.contains(&idx)));
+		}
+		assert!(spawned > 0);
+	}
+}
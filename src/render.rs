@@ -0,0 +1,122 @@
+use crate::WorldMap;
+
+// bump when the record layout or a kind's fields change, JS checks it in the header
+pub const RENDER_STATE_VERSION: u32 = 1;
+// missing cell, status or winner
+pub const RENDER_NONE: u32 = u32::MAX;
+
+// One entity of render_state(), 7 u32s so the whole buffer reads as one Uint32Array.
+// The first record is the header, the rest come in kind order: snakes, reward, trap, super bonus, enemies, portals, obstacles.
+//
+//   kind         cell        data
+//   HEADER       version     records (header included), status (GameStatus), winner, steps, bonus points (player 1)
+//   SNAKE        head        player, length, alive (0/1), points, bonus points
+//   REWARD       idx         points, color, coming bonus (player 1)
+//   TRAP         idx         life, color
+//   SUPER_BONUS  idx         life, points
+//   ENEMY        idx         life, status (TargetStatus), points
+//   PORTAL       entry       exit, pair
+//   OBSTACLE     idx         -
+//
+// Colors index the item palette, ["#FFEAAE", "chocolate", "blueviolet", "brown"] (RewardType order).
+// Unused data is 0, missing values are RENDER_NONE.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RenderRecord {
+	pub kind: u32,
+	pub cell: u32,
+	pub data: [u32; 5]
+}
+
+impl RenderRecord {
+	pub const HEADER: u32 = 0;
+	pub const SNAKE: u32 = 1;
+	pub const REWARD: u32 = 2;
+	pub const TRAP: u32 = 3;
+	pub const SUPER_BONUS: u32 = 4;
+	pub const ENEMY: u32 = 5;
+	pub const PORTAL: u32 = 6;
+	pub const OBSTACLE: u32 = 7;
+	pub const WORDS: usize = 7;
+
+	fn new(kind: u32, cell: usize, data: [usize; 5]) -> RenderRecord {
+		RenderRecord {
+			kind,
+			cell: cell as u32,
+			data: data.map(|value| value as u32)
+		}
+	}
+}
+
+fn or_none(value: Option<usize>) -> usize {
+	value.unwrap_or(RENDER_NONE as usize)
+}
+
+// the buffer is reused, no allocation once it has seen the most entities
+pub(crate) fn fill(world: &WorldMap, records: &mut Vec<RenderRecord>) {
+	records.clear();
+	let first = &world.players[0];
+	let status = world.status.map(|status| status as usize);
+	records.push(RenderRecord::new(RenderRecord::HEADER, RENDER_STATE_VERSION as usize,
+		[0, or_none(status), or_none(world.winner), first.steps, first.bonus_points]));
+
+	for (p, player) in world.players.iter().enumerate() {
+		records.push(RenderRecord::new(RenderRecord::SNAKE, player.head(),
+			[p, player.snake.body.len(), player.alive() as usize, player.points, player.bonus_points]));
+	}
+	if let Some(reward) = &world.reward_cell {
+		let color = reward.reward_type.map(|reward_type| reward_type as usize);
+		records.push(RenderRecord::new(RenderRecord::REWARD, reward.idx,
+			[reward.points, or_none(color), first.comming_bonus(world._steps), 0, 0]));
+	}
+	if let Some(trap) = &world.trap_cell {
+		let color = crate::ITEM_COLORS.iter().position(|color| *color == trap.color);
+		records.push(RenderRecord::new(RenderRecord::TRAP, trap.idx, [trap.life, or_none(color), 0, 0, 0]));
+	}
+	if let Some(bonus) = &world.super_bonus_cell {
+		records.push(RenderRecord::new(RenderRecord::SUPER_BONUS, bonus.0, [bonus.1, world.super_bonus_points(), 0, 0, 0]));
+	}
+	for enemy in &world.enemies {
+		let points = enemy.calculate_points(world.config.enemy_points_per_life);
+		records.push(RenderRecord::new(RenderRecord::ENEMY, enemy.idx, [enemy.life, enemy.status as usize, points, 0, 0]));
+	}
+	for (pair, (entry, exit)) in world.portals.iter().enumerate() {
+		records.push(RenderRecord::new(RenderRecord::PORTAL, *entry, [*exit, pair, 0, 0, 0]));
+	}
+	for idx in &world.obstacles {
+		records.push(RenderRecord::new(RenderRecord::OBSTACLE, *idx, [0; 5]));
+	}
+	records[0].data[0] = records.len() as u32;
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{Direction, GameStatus, MovingTarget, TargetStatus};
+
+	#[test]
+	fn records_follow_the_layout() {
+		let mut world = WorldMap::from_level_str_with_seed("#..P....\n..>..P..\n", 5).ok().unwrap();
+		let records = world.render_records().to_vec();
+		let header = records[0];
+		assert_eq!((header.kind, header.cell), (RenderRecord::HEADER, RENDER_STATE_VERSION));
+		assert_eq!(header.data[0] as usize, records.len());
+		// not started, no winner
+		assert_eq!((header.data[1], header.data[2]), (RENDER_NONE, RENDER_NONE));
+		let kinds: Vec<u32> = records.iter().map(|record| record.kind).collect();
+		assert_eq!(kinds, [RenderRecord::HEADER, RenderRecord::SNAKE, RenderRecord::REWARD, RenderRecord::PORTAL, RenderRecord::OBSTACLE]);
+		assert_eq!(records[1].cell, 10);
+		assert_eq!(records[1].data[..3], [0, world.config().snake_start_size as u32, 1]);
+		assert_eq!((records[3].cell, records[3].data[..2].to_vec()), (3, vec!(13, 0)));
+		assert_eq!(records[4].cell, 0);
+
+		world.start_game();
+		world.enemies.push(MovingTarget { idx: 6, direction: Direction::Up, points: 100, life: 40, decision_steps: 10, steps_to_move: 0, status: TargetStatus::Good });
+		let records = world.render_records();
+		assert_eq!(records[0].data[1], GameStatus::Played as u32);
+		assert_eq!(records[0].data[0] as usize, records.len());
+		let enemy = records.iter().find(|record| record.kind == RenderRecord::ENEMY).unwrap();
+		assert_eq!((enemy.cell, enemy.data[0], enemy.data[1]), (6, 40, TargetStatus::Good as u32));
+		assert!(records.windows(2).all(|pair| pair[0].kind <= pair[1].kind));
+	}
+}
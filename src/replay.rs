@@ -0,0 +1,256 @@
+use serde::{Deserialize, Serialize};
+
+use crate::codec::{ByteReader, ByteWriter, DecodeError};
+use crate::{BoundaryMode, ControllerKind, Direction, GameConfig, GameStat, Level, ScriptedInput, Simulation, WorldMap, MAX_CELLS};
+
+const MAGIC: &[u8; 4] = b"SNKR";
+const VERSION: u8 = 1;
+
+// Everything needed to play a seeded game again: the seed, the level, the rules,
+// every direction change with its tick and how many ticks were played.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+	pub seed: u64,
+	pub level: Level,
+	#[serde(default)]
+	pub config: GameConfig,
+	pub ticks: usize,
+	pub inputs: Vec<ScriptedInput>
+}
+
+impl Replay {
+	pub fn new(seed: u64, level: Level) -> Replay {
+		Replay {
+			seed,
+			level,
+			config: GameConfig::default(),
+			ticks: 0,
+			inputs: vec!()
+		}
+	}
+
+	pub fn record(&mut self, tick: usize, player: usize, direction: Direction) {
+		self.inputs.push(ScriptedInput { tick, direction, player });
+	}
+
+	// simulation at tick 0, step it to debug the run move by move
+	pub fn simulation(&self) -> Simulation {
+		let world = WorldMap::from_level_with_config(&self.level, self.config, Some(self.seed));
+		Simulation::with_inputs(world, self.inputs.clone())
+	}
+
+	pub fn run(&self) -> GameStat {
+		let mut simulation = self.simulation();
+		simulation.run(self.ticks);
+		simulation.stat()
+	}
+
+	// tick delta and direction share one varint: (delta << 2) | direction, the player follows it
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut writer = ByteWriter::new(MAGIC, VERSION);
+		writer.u64(self.seed);
+		writer.varint(self.level.width);
+		writer.varint(self.level.height);
+		writer.varint(self.level.snake_idx);
+		match self.level.snake_direction {
+			Some(direction) => writer.u8(direction as u8 + 1),
+			None => writer.u8(0)
+		}
+		writer.u8(self.level.boundary_mode as u8);
+		writer.varint(self.level.obstacles.len());
+		for idx in &self.level.obstacles {
+			writer.varint(*idx);
+		}
+		writer.varint(self.level.portals.len());
+		for (entry, exit) in &self.level.portals {
+			writer.varint(*entry);
+			writer.varint(*exit);
+		}
+		writer.varint(self.level.players.len());
+		for (idx, direction) in &self.level.players {
+			writer.varint(*idx);
+			writer.u8(*direction as u8);
+		}
+		writer.varint(self.level.controllers.len());
+		for (player, controller) in &self.level.controllers {
+			writer.varint(*player);
+			writer.u8(*controller as u8);
+		}
+		self.config.write(&mut writer);
+		writer.varint(self.ticks);
+		let mut inputs = self.inputs.clone();
+		inputs.sort_by_key(|input| input.tick);
+		writer.varint(inputs.len());
+		let mut last_tick = 0;
+		for input in &inputs {
+			writer.varint((input.tick - last_tick) << 2 | input.direction as usize);
+			writer.varint(input.player);
+			last_tick = input.tick;
+		}
+		writer.finish()
+	}
+
+	pub fn from_bytes(bytes: &[u8]) -> Result<Replay, DecodeError> {
+		let (mut reader, version) = ByteReader::new(bytes, MAGIC)?;
+		if version != VERSION {
+			return Err(DecodeError::UnsupportedVersion(version));
+		}
+		let seed = reader.u64()?;
+		let mut level = Level::new(reader.varint()?, reader.varint()?, reader.varint()?);
+		level.snake_direction = match reader.u8()? {
+			0 => None,
+			idx => Some(Direction::from_index(idx as usize - 1).ok_or(DecodeError::Invalid("direction"))?)
+		};
+		level.boundary_mode = BoundaryMode::from_index(reader.u8()? as usize).ok_or(DecodeError::Invalid("boundary mode"))?;
+		let obstacle_count = reader.list_len(1)?;
+		for _ in 0..obstacle_count {
+			level.obstacles.push(reader.varint()?);
+		}
+		let portal_count = reader.list_len(2)?;
+		for _ in 0..portal_count {
+			level.portals.push((reader.varint()?, reader.varint()?));
+		}
+		let player_count = reader.list_len(2)?;
+		for _ in 0..player_count {
+			let idx = reader.varint()?;
+			let direction = Direction::from_index(reader.u8()? as usize).ok_or(DecodeError::Invalid("direction"))?;
+			level.players.push((idx, direction));
+		}
+		let controller_count = reader.list_len(2)?;
+		for _ in 0..controller_count {
+			let player = reader.varint()?;
+			let controller = ControllerKind::from_index(reader.u8()? as usize).ok_or(DecodeError::Invalid("controller"))?;
+			level.controllers.push((player, controller));
+		}
+		let mut replay = Replay::new(seed, level);
+		replay.config = GameConfig::read(&mut reader)?;
+		replay.ticks = reader.varint()?;
+		let count = reader.list_len(2)?;
+		let mut tick: usize = 0;
+		for _ in 0..count {
+			let packed = reader.varint()?;
+			tick = tick.checked_add(packed >> 2).ok_or(DecodeError::Invalid("input tick"))?;
+			let direction = Direction::from_index(packed & 0b11).ok_or(DecodeError::Invalid("direction"))?;
+			replay.record(tick, reader.varint()?, direction);
+		}
+		reader.finish()?;
+		check_level(&replay.level)?;
+		Ok(replay)
+	}
+
+	pub fn to_json(&self) -> String {
+		serde_json::to_string(self).unwrap_or_default()
+	}
+
+	pub fn from_json(json: &str) -> Result<Replay, DecodeError> {
+		let mut replay: Replay = serde_json::from_str(json).map_err(|err| DecodeError::Json(err.to_string()))?;
+		replay.inputs.sort_by_key(|input| input.tick);
+		check_level(&replay.level)?;
+		Ok(replay)
+	}
+}
+
+// Shared replays are outside input, run() sets the level up without questions.
+// Obstacles and portals on a snake are dropped there, cells off the board would panic.
+fn check_level(level: &Level) -> Result<(), DecodeError> {
+	let cells = level.width.checked_mul(level.height)
+		.filter(|cells| (2..=MAX_CELLS).contains(cells))
+		.ok_or(DecodeError::Invalid("level size"))?;
+	let spawns = level.players.iter().map(|(idx, _)| *idx);
+	let items = level.obstacles.iter().copied().chain(level.portals.iter().flat_map(|(entry, exit)| [*entry, *exit]));
+	if std::iter::once(level.snake_idx).chain(spawns).chain(items).any(|idx| idx >= cells) {
+		return Err(DecodeError::Invalid("level cell"));
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn summary(stat: &GameStat) -> [usize; 9] {
+		[stat.consumed_rewards, stat.consumed_traps, stat.life_steps, stat.bonus, stat.snake_size,
+			stat.super_bonuses, stat.consumed_moving_targets, stat.points, stat.eaten_by_enemy]
+	}
+
+	// the autopilot's turns are recorded like key presses
+	fn played_game(seed: u64) -> WorldMap {
+		let mut world = WorldMap::new_with_seed(10, 45, seed);
+		world.set_autopilot(true);
+		world.start_game();
+		for _ in 0..3000 {
+			if world.game_status() != Some(crate::GameStatus::Played) {
+				break;
+			}
+			world.update();
+		}
+		world
+	}
+
+	#[test]
+	fn replay_ends_like_the_game() {
+		for seed in 1..6 {
+			let world = played_game(seed);
+			let replay = world.replay().unwrap();
+			assert!(!replay.inputs.is_empty());
+			assert_eq!(summary(&replay.run()), summary(&world.get_game_stat()));
+		}
+	}
+
+	#[test]
+	fn board_changes_stay_in_the_replay() {
+		let mut world = WorldMap::new_with_seed(10, 45, 8);
+		assert!(world.set_size(14));
+		assert!(world.set_boundary_mode(BoundaryMode::Walls));
+		assert!(world.set_obstacles(vec![3, 150]));
+		assert!(world.set_portals(vec![20, 180]));
+		world.set_autopilot(true);
+		world.start_game();
+		for _ in 0..50 {
+			world.update();
+		}
+		// the replay only knows the board the game started on
+		assert!(!world.add_obstacle(100));
+		assert!(!world.set_size(16));
+		assert!(!world.set_boundary_mode(BoundaryMode::Wrap));
+		assert!(!world.set_portals(vec![30, 190]));
+		for _ in 0..3000 {
+			world.update();
+		}
+		assert_eq!(world.obstacle_count(), 2);
+		assert_eq!(summary(&world.replay().unwrap().run()), summary(&world.get_game_stat()));
+	}
+
+	#[test]
+	fn bytes_and_json_round_trip() {
+		let mut replay = played_game(11).replay().unwrap();
+		replay.level.obstacles = vec![0, 9];
+		replay.level.portals = vec![(20, 29)];
+		replay.level.boundary_mode = BoundaryMode::Walls;
+
+		let from_bytes = Replay::from_bytes(&replay.to_bytes()).ok().unwrap();
+		assert!(from_bytes == replay);
+		let from_json = Replay::from_json(&replay.to_json()).ok().unwrap();
+		assert!(from_json == replay);
+	}
+
+	#[test]
+	fn broken_replays_are_rejected() {
+		let bytes = played_game(2).replay().unwrap().to_bytes();
+		for len in 0..bytes.len() {
+			assert!(Replay::from_bytes(&bytes[..len]).is_err());
+		}
+		let mut magic = bytes.clone();
+		magic[0] = b'X';
+		assert_eq!(Replay::from_bytes(&magic).err(), Some(DecodeError::BadMagic));
+
+		let empty = Replay::new(1, Level::new(0, 5, 0));
+		assert_eq!(Replay::from_bytes(&empty.to_bytes()).err(), Some(DecodeError::Invalid("level size")));
+		assert_eq!(Replay::from_json(&empty.to_json()).err(), Some(DecodeError::Invalid("level size")));
+		let mut off_board = Replay::new(1, Level::new(4, 4, 5));
+		off_board.level.obstacles.push(16);
+		assert_eq!(Replay::from_bytes(&off_board.to_bytes()).err(), Some(DecodeError::Invalid("level cell")));
+		assert_eq!(Replay::from_json(&off_board.to_json()).err(), Some(DecodeError::Invalid("level cell")));
+		assert!(matches!(Replay::from_json("{\"seed\": 1}"), Err(DecodeError::Json(_))));
+	}
+}
@@ -0,0 +1,106 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen(module = "/front/utils/rnd.js")]
+extern {
+	fn rnd(num: usize) -> usize;
+}
+
+// Source of every random decision made by the game.
+// rnd(max) returns a number in 0..max, and 0 when max is 0.
+pub trait GameRng {
+	fn rnd(&mut self, max: usize) -> usize;
+
+	// internal state for snapshots, None if the rng can't be restored
+	fn state(&self) -> Option<u64> {
+		None
+	}
+}
+
+// Math.random() from the browser, games are not reproducible with it
+#[cfg(feature = "wasm")]
+pub struct JsRng;
+
+#[cfg(feature = "wasm")]
+impl GameRng for JsRng {
+	fn rnd(&mut self, max: usize) -> usize {
+		rnd(max)
+	}
+}
+
+// rng used by WorldMap::new when no seed is given
+#[cfg(feature = "wasm")]
+pub(crate) fn default_rng() -> Box<dyn GameRng> {
+	Box::new(JsRng)
+}
+
+#[cfg(not(feature = "wasm"))]
+pub(crate) fn default_rng() -> Box<dyn GameRng> {
+	let seed = std::time::SystemTime::now()
+		.duration_since(std::time::UNIX_EPOCH)
+		.map(|time| time.as_nanos() as u64)
+		.unwrap_or(0);
+	Box::new(SeededRng::new(seed))
+}
+
+// SplitMix64, small and fast. Same seed -> same sequence on every platform.
+#[derive(Clone)]
+pub struct SeededRng {
+	state: u64
+}
+
+impl SeededRng {
+	pub fn new(seed: u64) -> SeededRng {
+		SeededRng { state: seed }
+	}
+
+	pub fn next_u64(&mut self) -> u64 {
+		self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+		let mut z = self.state;
+		z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+		z ^ (z >> 31)
+	}
+}
+
+impl GameRng for SeededRng {
+	fn rnd(&mut self, max: usize) -> usize {
+		if max == 0 {
+			return 0;
+		}
+		(self.next_u64() % max as u64) as usize
+	}
+
+	fn state(&self) -> Option<u64> {
+		Some(self.state)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn same_seed_same_sequence() {
+		let mut a = SeededRng::new(42);
+		let mut b = SeededRng::new(42);
+		let first: Vec<usize> = (0..100).map(|_| a.rnd(1000)).collect();
+		let second: Vec<usize> = (0..100).map(|_| b.rnd(1000)).collect();
+		assert_eq!(first, second);
+		assert!(first.iter().all(|n| *n < 1000));
+
+		let mut other = SeededRng::new(43);
+		let third: Vec<usize> = (0..100).map(|_| other.rnd(1000)).collect();
+		assert_ne!(first, third);
+	}
+
+	#[test]
+	fn state_restores_the_sequence() {
+		let mut rng = SeededRng::new(7);
+		rng.rnd(10);
+		let mut restored = SeededRng::new(rng.state().unwrap());
+		assert_eq!(rng.next_u64(), restored.next_u64());
+		assert_eq!(rng.rnd(0), 0);
+	}
+}
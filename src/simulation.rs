@@ -0,0 +1,123 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Direction, GameStat, GameStatus, WorldMap};
+
+// direction change applied right before the update of the given tick
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ScriptedInput {
+	pub tick: usize,
+	pub direction: Direction,
+	// index of the snake, player 1 is 0
+	#[serde(default)]
+	pub player: usize
+}
+
+// Headless driver, steps a WorldMap without any browser around it.
+pub struct Simulation {
+	world: WorldMap,
+	inputs: Vec<ScriptedInput>,
+	next_input: usize,
+	tick: usize
+}
+
+impl Simulation {
+	pub fn new(world: WorldMap) -> Simulation {
+		Simulation::with_inputs(world, vec!())
+	}
+
+	pub fn with_inputs(mut world: WorldMap, mut inputs: Vec<ScriptedInput>) -> Simulation {
+		inputs.sort_by_key(|input| input.tick); // stable, same tick keeps its order
+		world.start_game();
+		Simulation {
+			world,
+			inputs,
+			next_input: 0,
+			tick: 0
+		}
+	}
+
+	pub fn push_input(&mut self, direction: Direction) {
+		self.push_player_input(0, direction);
+	}
+
+	pub fn push_player_input(&mut self, player: usize, direction: Direction) {
+		let tick = self.tick;
+		let at = self.next_input + self.inputs[self.next_input..].partition_point(|input| input.tick <= tick);
+		self.inputs.insert(at, ScriptedInput { tick, direction, player });
+	}
+
+	pub fn is_running(&self) -> bool {
+		matches!(self.world.game_status(), Some(GameStatus::Played))
+	}
+
+	// one update tick, returns false once the game is over
+	pub fn step(&mut self) -> bool {
+		if !self.is_running() {
+			return false;
+		}
+		while let Some(input) = self.inputs.get(self.next_input) {
+			if input.tick > self.tick {
+				break;
+			}
+			self.world.change_player_direction(input.player, input.direction);
+			self.next_input += 1;
+		}
+		self.world.update();
+		self.tick += 1;
+		self.is_running()
+	}
+
+	// same pacing as WorldMap::advance, scripted inputs are applied as usual
+	pub fn advance(&mut self, elapsed_ms: usize) -> usize {
+		self.world.add_elapsed(elapsed_ms);
+		let start = self.tick;
+		while self.world.take_due_tick() {
+			self.step();
+		}
+		self.tick - start
+	}
+
+	// runs until the game ends or max_ticks were played, returns played ticks
+	pub fn run(&mut self, max_ticks: usize) -> usize {
+		let start = self.tick;
+		while self.tick - start < max_ticks && self.step() {}
+		self.tick - start
+	}
+
+	pub fn tick(&self) -> usize {
+		self.tick
+	}
+
+	pub fn world(&self) -> &WorldMap {
+		&self.world
+	}
+
+	pub fn world_mut(&mut self) -> &mut WorldMap {
+		&mut self.world
+	}
+
+	pub fn into_world(self) -> WorldMap {
+		self.world
+	}
+
+	pub fn stat(&self) -> GameStat {
+		self.world.get_game_stat()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn advance_applies_scripted_inputs() {
+		let world = WorldMap::from_level_str_with_seed("........\n........\n..>.....\n........\n", 5).ok().unwrap();
+		let inputs = vec!(ScriptedInput { tick: 1, direction: Direction::Down, player: 0 });
+		let mut sim = Simulation::with_inputs(world, inputs);
+		let interval = sim.world().tick_interval_ms();
+		assert_eq!(sim.advance(interval - 1), 0);
+		assert_eq!(sim.advance(interval + 1), 2);
+		assert_eq!(sim.tick(), 2);
+		assert_eq!(sim.world().snake_head_index(), 27);
+	}
+}
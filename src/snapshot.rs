@@ -0,0 +1,452 @@
+use std::collections::VecDeque;
+
+use crate::body::SnakeBody;
+use crate::codec::{ByteReader, ByteWriter, DecodeError};
+use crate::grid::{self, Occupancy};
+use crate::rng;
+use crate::{
+	blocked_cells, MAX_CELLS, MAX_QUEUED_TURNS,
+	BoundaryMode, ControllerKind, Direction, GameConfig, GameStatus, MovingTarget, Player, Reason, Replay, RewardCell, RewardType, SeededRng, Snake, SnakeCell,
+	SuperBonus, TargetStatus, TrapCell, WorldMap
+};
+
+const MAGIC: &[u8; 4] = b"SNKS";
+const VERSION: u8 = 1;
+
+// Whole game state, a loaded snapshot plays on exactly like the saved game
+// as long as the game was seeded (browser Math.random can't be restored).
+pub(crate) fn encode(world: &WorldMap) -> Vec<u8> {
+	let mut writer = ByteWriter::new(MAGIC, VERSION);
+	writer.varint(world.width);
+	writer.varint(world.height);
+	writer.u8(world.boundary_mode as u8);
+	writer.varint(world.obstacles.len());
+	for idx in &world.obstacles {
+		writer.varint(*idx);
+	}
+	writer.varint(world.portals.len());
+	for (entry, exit) in &world.portals {
+		writer.varint(*entry);
+		writer.varint(*exit);
+	}
+
+	writer.varint(world.players.len());
+	for player in &world.players {
+		writer.u8(player.snake.direction as u8);
+		writer.varint(player.snake.body.len());
+		for cell in &player.snake.body {
+			writer.varint(cell.0);
+		}
+		writer.varint(player.turns.len());
+		for direction in &player.turns {
+			writer.u8(*direction as u8);
+		}
+		writer.u8(player.reason as u8);
+		writer.varint(player.points);
+		writer.varint(player.steps);
+		writer.varint(player.bonus_points);
+		writer.varint(player.max_snake_size);
+		writer.varint(player.consumed_rewards);
+		writer.varint(player.consumed_traps);
+		writer.varint(player.consumed_super_bonuses);
+		writer.varint(player.consumed_moving_targets);
+		writer.varint(player.eaten_by_enemy);
+		write_option(&mut writer, &player.controller, |writer, controller| writer.u8(*controller as u8));
+		writer.bool(player.autopilot);
+	}
+
+	write_option(&mut writer, &world.reward_cell, |writer, reward| {
+		writer.varint(reward.idx);
+		write_option(writer, &reward.reward_type, |writer, reward_type| writer.u8(*reward_type as u8));
+		writer.varint(reward.points);
+	});
+
+	write_option(&mut writer, &world.status, |writer, status| writer.u8(*status as u8));
+	write_option(&mut writer, &world.winner, |writer, winner| writer.varint(*winner));
+	writer.varint(world._steps);
+	writer.varint(world.life_steps);
+
+	writer.varint(world.trap_steps);
+	write_option(&mut writer, &world.trap_cell, |writer, trap| {
+		writer.varint(trap.idx);
+		writer.varint(trap.life);
+		writer.string(&trap.color);
+	});
+
+	writer.varint(world.super_bonus_steps);
+	write_option(&mut writer, &world.super_bonus_cell, |writer, bonus| {
+		writer.varint(bonus.0);
+		writer.varint(bonus.1);
+	});
+
+	writer.varint(world.steps_to_moving_target);
+	writer.varint(world.enemies.len());
+	for target in &world.enemies {
+		writer.varint(target.idx);
+		writer.u8(target.direction as u8);
+		writer.varint(target.points);
+		writer.varint(target.life);
+		writer.varint(target.decision_steps);
+		writer.varint(target.steps_to_move);
+		writer.u8(target.status as u8);
+	}
+
+	world.config.write(&mut writer);
+	writer.varint(world.elapsed_ms);
+
+	write_option(&mut writer, &world.rng.state(), |writer, state| writer.u64(*state));
+	write_option(&mut writer, &world.replay, |writer, replay| writer.bytes(&replay.to_bytes()));
+	writer.finish()
+}
+
+pub(crate) fn decode(bytes: &[u8]) -> Result<WorldMap, DecodeError> {
+	let (mut reader, version) = ByteReader::new(bytes, MAGIC)?;
+	if version != VERSION {
+		return Err(DecodeError::UnsupportedVersion(version));
+	}
+	let width = reader.varint()?;
+	let height = reader.varint()?;
+	if width == 0 || height == 0 || width.checked_mul(height).is_none_or(|cells| cells > MAX_CELLS) {
+		return Err(DecodeError::Invalid("map size"));
+	}
+	let boundary_mode = BoundaryMode::from_index(reader.u8()? as usize).ok_or(DecodeError::Invalid("boundary mode"))?;
+	let cells = width * height;
+	let cell = |reader: &mut ByteReader| -> Result<usize, DecodeError> {
+		let idx = reader.varint()?;
+		if idx >= cells {
+			return Err(DecodeError::Invalid("cell index"));
+		}
+		Ok(idx)
+	};
+	let obstacle_count = reader.list_len(1)?;
+	let mut obstacles = Vec::with_capacity(obstacle_count);
+	for _ in 0..obstacle_count {
+		obstacles.push(cell(&mut reader)?);
+	}
+	if obstacles.windows(2).any(|pair| pair[0] >= pair[1]) {
+		return Err(DecodeError::Invalid("obstacles"));
+	}
+	let portal_count = reader.list_len(2)?;
+	let mut portals = Vec::with_capacity(portal_count);
+	for _ in 0..portal_count {
+		portals.push((cell(&mut reader)?, cell(&mut reader)?));
+	}
+
+	let player_count = reader.list_len(16)?;
+	if player_count == 0 {
+		return Err(DecodeError::Invalid("players"));
+	}
+	let mut players = Vec::with_capacity(player_count);
+	for _ in 0..player_count {
+		let direction = read_direction(&mut reader)?;
+		let body_len = reader.list_len(1)?;
+		// a snake over the whole board that just grew holds its last cell twice
+		if body_len < 2 || body_len > cells + 1 {
+			return Err(DecodeError::Invalid("snake body"));
+		}
+		let mut body = Vec::with_capacity(body_len);
+		for _ in 0..body_len {
+			body.push(SnakeCell(cell(&mut reader)?));
+		}
+		let turn_count = reader.list_len(1)?;
+		if turn_count > MAX_QUEUED_TURNS {
+			return Err(DecodeError::Invalid("queued turns"));
+		}
+		let mut turns = VecDeque::with_capacity(turn_count);
+		for _ in 0..turn_count {
+			turns.push_back(read_direction(&mut reader)?);
+		}
+		let player = Player {
+			snake: Snake { body: SnakeBody::new(&body), direction },
+			turns,
+			reason: read_reason(&mut reader)?,
+			points: reader.varint()?,
+			steps: reader.varint()?,
+			bonus_points: reader.varint()?,
+			max_snake_size: reader.varint()?,
+			consumed_rewards: reader.varint()?,
+			consumed_traps: reader.varint()?,
+			consumed_super_bonuses: reader.varint()?,
+			consumed_moving_targets: reader.varint()?,
+			eaten_by_enemy: reader.varint()?,
+			controller: read_option(&mut reader, |reader| {
+				ControllerKind::from_index(reader.u8()? as usize).ok_or(DecodeError::Invalid("controller"))
+			})?,
+			autopilot: reader.bool()?
+		};
+		// update() counts these down before checking them
+		if player.alive() && player.steps == 0 {
+			return Err(DecodeError::Invalid("player steps"));
+		}
+		players.push(player);
+	}
+
+	let reward_cell = read_option(&mut reader, |reader| {
+		Ok(RewardCell {
+			idx: cell(reader)?,
+			reward_type: read_option(reader, read_reward_type)?,
+			points: reader.varint()?
+		})
+	})?;
+
+	let status = read_option(&mut reader, read_game_status)?;
+	let winner = read_option(&mut reader, |reader| reader.varint())?;
+	if winner.is_some_and(|winner| winner >= player_count) {
+		return Err(DecodeError::Invalid("winner"));
+	}
+	let _steps = reader.varint()?;
+	if _steps == 0 {
+		return Err(DecodeError::Invalid("reward steps"));
+	}
+	let life_steps = reader.varint()?;
+
+	let trap_steps = reader.varint()?;
+	let trap_cell = read_option(&mut reader, |reader| {
+		Ok(TrapCell::new(cell(reader)?, reader.varint()?, reader.string()?))
+	})?;
+	if trap_cell.as_ref().is_some_and(|trap| trap.life == 0) {
+		return Err(DecodeError::Invalid("trap life"));
+	}
+
+	let super_bonus_steps = reader.varint()?;
+	if super_bonus_steps == 0 {
+		return Err(DecodeError::Invalid("super bonus steps"));
+	}
+	let super_bonus_cell = read_option(&mut reader, |reader| {
+		Ok(SuperBonus(cell(reader)?, reader.varint()?))
+	})?;
+	if super_bonus_cell.as_ref().is_some_and(|bonus| bonus.1 == 0) {
+		return Err(DecodeError::Invalid("super bonus life"));
+	}
+
+	let steps_to_moving_target = reader.varint()?;
+	let enemy_count = reader.list_len(7)?;
+	if enemy_count > cells {
+		return Err(DecodeError::Invalid("enemies"));
+	}
+	let mut enemies = Vec::with_capacity(enemy_count);
+	for _ in 0..enemy_count {
+		enemies.push(MovingTarget {
+			idx: cell(&mut reader)?,
+			direction: read_direction(&mut reader)?,
+			points: reader.varint()?,
+			life: reader.varint()?,
+			decision_steps: reader.varint()?,
+			steps_to_move: reader.varint()?,
+			status: read_target_status(&mut reader)?
+		});
+	}
+
+	let config = GameConfig::read(&mut reader)?;
+	let elapsed_ms = reader.varint()?;
+
+	let rng = match read_option(&mut reader, |reader| reader.u64())? {
+		Some(state) => Box::new(SeededRng::new(state)),
+		None => rng::default_rng()
+	};
+	let replay = read_option(&mut reader, |reader| Replay::from_bytes(reader.bytes()?))?;
+	reader.finish()?;
+	let snake_cells = players.iter().flat_map(|player| &player.snake.body).map(|cell| cell.0);
+	if crowded(cells, snake_cells) || crowded(cells, enemies.iter().map(|enemy| enemy.idx)) {
+		return Err(DecodeError::Invalid("crowded cell"));
+	}
+
+	let mut world = WorldMap {
+		width,
+		height,
+		players,
+		reward_cell,
+		status,
+		winner,
+		_steps,
+		trap_steps,
+		trap_cell,
+		life_steps,
+		super_bonus_cell,
+		super_bonus_steps,
+		enemies,
+		steps_to_moving_target,
+		boundary_mode,
+		blocked_cells: blocked_cells(&obstacles, &portals),
+		grid: Occupancy::new(0),
+		obstacles,
+		portals,
+		config,
+		elapsed_ms,
+		events: VecDeque::new(), // events are not game state, a loaded game starts with none
+		rng,
+		replay,
+		// whether the board was edited isn't saved, a game that hasn't started plays it safe
+		board_edited: status.is_none(),
+		render: vec!()
+	};
+	world.rebuild_grid();
+	Ok(world)
+}
+
+// more on one cell than the occupancy grid can count
+fn crowded(cells: usize, indexes: impl Iterator<Item = usize>) -> bool {
+	let mut counts = vec![0; cells];
+	indexes.into_iter().any(|idx| {
+		counts[idx] += 1;
+		counts[idx] > grid::MAX_PER_CELL
+	})
+}
+
+fn write_option<T>(writer: &mut ByteWriter, value: &Option<T>, write: impl FnOnce(&mut ByteWriter, &T)) {
+	writer.bool(value.is_some());
+	if let Some(value) = value {
+		write(writer, value);
+	}
+}
+
+fn read_option<'a, T>(
+	reader: &mut ByteReader<'a>,
+	read: impl FnOnce(&mut ByteReader<'a>) -> Result<T, DecodeError>
+) -> Result<Option<T>, DecodeError> {
+	if reader.bool()? {
+		Ok(Some(read(reader)?))
+	} else {
+		Ok(None)
+	}
+}
+
+fn read_direction(reader: &mut ByteReader) -> Result<Direction, DecodeError> {
+	Direction::from_index(reader.u8()? as usize).ok_or(DecodeError::Invalid("direction"))
+}
+
+fn read_reward_type(reader: &mut ByteReader) -> Result<RewardType, DecodeError> {
+	match reader.u8()? {
+		0 => Ok(RewardType::Yellow),
+		1 => Ok(RewardType::Red),
+		2 => Ok(RewardType::Blue),
+		3 => Ok(RewardType::Black),
+		_ => Err(DecodeError::Invalid("reward type"))
+	}
+}
+
+fn read_game_status(reader: &mut ByteReader) -> Result<GameStatus, DecodeError> {
+	match reader.u8()? {
+		0 => Ok(GameStatus::Won),
+		1 => Ok(GameStatus::Lost),
+		2 => Ok(GameStatus::Played),
+		_ => Err(DecodeError::Invalid("game status"))
+	}
+}
+
+fn read_reason(reader: &mut ByteReader) -> Result<Reason, DecodeError> {
+	match reader.u8()? {
+		0 => Ok(Reason::StillAlive),
+		1 => Ok(Reason::Eaten),
+		2 => Ok(Reason::NotActive),
+		3 => Ok(Reason::Suiside),
+		4 => Ok(Reason::HitWall),
+		5 => Ok(Reason::HitObstacle),
+		6 => Ok(Reason::HitSnake),
+		_ => Err(DecodeError::Invalid("reason"))
+	}
+}
+
+fn read_target_status(reader: &mut ByteReader) -> Result<TargetStatus, DecodeError> {
+	match reader.u8()? {
+		0 => Ok(TargetStatus::VeryHungry),
+		1 => Ok(TargetStatus::Hungry),
+		2 => Ok(TargetStatus::Fine),
+		3 => Ok(TargetStatus::Good),
+		4 => Ok(TargetStatus::VeryGood),
+		_ => Err(DecodeError::Invalid("target status"))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn played_game(seed: u64, ticks: usize) -> WorldMap {
+		let mut world = WorldMap::new_with_seed(12, 70, seed);
+		world.set_autopilot(true);
+		world.start_game();
+		for _ in 0..ticks {
+			world.update();
+		}
+		world
+	}
+
+	fn decode_error(world: &WorldMap) -> Option<DecodeError> {
+		decode(&encode(world)).err()
+	}
+
+	#[test]
+	fn loaded_game_plays_on_the_same() {
+		for seed in 1..6 {
+			let mut world = played_game(seed, 150);
+			let mut loaded = decode(&encode(&world)).ok().unwrap();
+			assert_eq!(encode(&loaded), encode(&world));
+			for _ in 0..500 {
+				world.update();
+				loaded.update();
+			}
+			assert_eq!(encode(&loaded), encode(&world));
+		}
+	}
+
+	#[test]
+	fn corrupt_input_is_an_error() {
+		let bytes = encode(&played_game(3, 100));
+		for len in 0..bytes.len() {
+			assert!(decode(&bytes[..len]).is_err());
+		}
+		let mut magic = bytes.clone();
+		magic[1] = b'X';
+		assert_eq!(decode(&magic).err(), Some(DecodeError::BadMagic));
+		let mut version = bytes.clone();
+		version[4] = VERSION + 1;
+		assert_eq!(decode(&version).err(), Some(DecodeError::UnsupportedVersion(VERSION + 1)));
+
+		let mut huge = ByteWriter::new(MAGIC, VERSION);
+		huge.varint(1 << 15);
+		huge.varint(1 << 15);
+		assert_eq!(decode(&huge.finish()).err(), Some(DecodeError::Invalid("map size")));
+	}
+
+	#[test]
+	fn crowded_cells_are_rejected() {
+		let mut world = played_game(5, 0);
+		world.players[0].snake.body = SnakeBody::new(&vec![SnakeCell(0); 200]);
+		assert_eq!(decode_error(&world), Some(DecodeError::Invalid("snake body")));
+
+		// every snake fits the board, together they stack past what a cell can count
+		let mut world = WorldMap::new_with_seed(300, 0, 1);
+		let body = vec![SnakeCell(0); 40000];
+		world.players[0].snake.body = SnakeBody::new(&body);
+		assert!(world.add_player(500, Direction::Up));
+		world.players[1].snake.body = SnakeBody::new(&body);
+		assert_eq!(decode_error(&world), Some(DecodeError::Invalid("crowded cell")));
+	}
+
+	#[test]
+	fn zero_counters_are_rejected() {
+		let world = played_game(4, 20);
+		let free = world.grid.spawn_cell(&mut SeededRng::new(1)).unwrap();
+
+		let mut broken = decode(&encode(&world)).ok().unwrap();
+		broken.players[0].steps = 0;
+		assert_eq!(decode_error(&broken), Some(DecodeError::Invalid("player steps")));
+
+		let mut broken = decode(&encode(&world)).ok().unwrap();
+		broken._steps = 0;
+		assert_eq!(decode_error(&broken), Some(DecodeError::Invalid("reward steps")));
+
+		let mut broken = decode(&encode(&world)).ok().unwrap();
+		broken.trap_cell = Some(TrapCell { idx: free, life: 0, color: String::from("brown") });
+		assert_eq!(decode_error(&broken), Some(DecodeError::Invalid("trap life")));
+
+		let mut broken = decode(&encode(&world)).ok().unwrap();
+		broken.super_bonus_steps = 0;
+		assert_eq!(decode_error(&broken), Some(DecodeError::Invalid("super bonus steps")));
+
+		let mut broken = decode(&encode(&world)).ok().unwrap();
+		broken.super_bonus_cell = Some(SuperBonus(free, 0));
+		assert_eq!(decode_error(&broken), Some(DecodeError::Invalid("super bonus life")));
+	}
+}